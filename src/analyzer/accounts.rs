@@ -1,52 +1,153 @@
+use super::constraints::{AccountField, AccountsStruct, ConstraintKind};
+use super::context::{
+    collect_fns, compact_tokens, compares, compares_in_macro, compares_key, field_writes, flatten,
+    is_comparison, tokens_contain, track_scope, FnBody, FnContext, Scope,
+};
+use super::index::CrateIndex;
+use super::pda::TRACE_DEPTH;
+use super::program::{BoundHandler, ProgramModel};
 use super::{Finding, Severity};
+use quote::ToTokens;
+use syn::spanned::Spanned;
 use syn::visit::Visit;
-use syn::{Attribute, File, ItemStruct};
+use syn::{BinOp, Expr, ExprBinary, ExprCall, File, ItemStruct, Macro, Type};
+
+/// Field names that conventionally hold the account allowed to act on an
+/// instruction.
+const AUTHORITY_NAMES: &[&str] = &["authority", "admin", "owner", "payer"];
+
+/// Sysvars that can be read with `from_account_info`.
+const SYSVAR_TYPES: &[&str] = &[
+    "Clock",
+    "Rent",
+    "Instructions",
+    "RecentBlockhashes",
+    "EpochSchedule",
+    "SlotHashes",
+    "StakeHistory",
+    "Fees",
+];
+
+/// Field names that conventionally hold a sysvar account.
+const SYSVAR_NAMES: &[&str] = &[
+    "clock",
+    "rent",
+    "instructions",
+    "recent_blockhashes",
+    "epoch_schedule",
+    "slot_hashes",
+    "stake_history",
+];
+
+/// Deserializers that read raw bytes straight into a type without looking
+/// at the 8-byte Anchor discriminator.
+const RAW_DESERIALIZERS: &[&str] = &[
+    "try_from_slice",
+    "deserialize",
+    "try_deserialize_unchecked",
+    "from_bytes",
+    "try_from_bytes",
+];
 
 /// Checks for:
 /// 1. #[derive(Accounts)] structs with fields missing constraints
 /// 2. Raw AccountInfo<'info> usage where Account<'info, T> is safer
-pub fn check_account_validation(file: &File, path: &str, source: &str) -> Vec<Finding> {
+/// 3. Authority-like fields that are never required to sign
+/// 4. `#[account]` types deserialized without their discriminator (type cosplay)
+/// 5. Accounts that can be initialized twice (`init_if_needed` or hand-written
+///    init handlers without an "already initialized" guard)
+/// 6. Pairs of `mut` accounts of the same type that may alias each other
+/// 7. Sysvars read from raw accounts whose address is never checked
+/// 8. SPL token accounts and mints not tied to an expected mint or owner
+pub fn check_account_validation<'ast>(
+    file: &'ast File,
+    path: &str,
+    source: &str,
+    model: &ProgramModel<'ast>,
+    index: &CrateIndex,
+) -> Vec<Finding> {
     let mut visitor = AccountVisitor {
         path: path.to_string(),
         source,
         findings: Vec::new(),
+        model,
     };
     visitor.visit_file(file);
-    visitor.findings
+
+    // `#[account]` types may be declared in any file of the crate
+    let data_accounts: Vec<String> = index
+        .crate_structs(path)
+        .filter(|s| is_data_account(s))
+        .map(|s| s.ident.to_string())
+        .collect();
+    let mut cosplay = CosplayVisitor {
+        path: visitor.path.clone(),
+        data_accounts: &data_accounts,
+        findings: Vec::new(),
+        scope: Scope::in_module(index.module(path)),
+        annotation: None,
+    };
+    cosplay.visit_file(file);
+    let mut findings = visitor.findings;
+    findings.extend(cosplay.findings);
+
+    // `load_instruction_at` trusts whatever account it is handed
+    for func in collect_fns(file, index.module(path)) {
+        let mut calls = SysvarCallFinder::default();
+        func.visit(&mut calls);
+        for line in calls.unchecked_loads {
+            findings.push(Finding {
+                severity: Severity::High,
+                check: "sysvar-spoofing".into(),
+                message: "`load_instruction_at` does not verify that it is reading the \
+                          instructions sysvar, so a fake account can supply forged \
+                          instructions. Use `load_instruction_at_checked`."
+                    .into(),
+                file: path.to_string(),
+                line,
+                context: Some(func.name.clone()),
+            });
+        }
+    }
+    findings
+}
+
+/// `#[account]`, `#[account(zero_copy)]` or `#[zero_copy]`.
+pub fn is_data_account(node: &ItemStruct) -> bool {
+    node.attrs
+        .iter()
+        .any(|a| a.path().is_ident("account") || a.path().is_ident("zero_copy"))
 }
 
-struct AccountVisitor<'a> {
+struct AccountVisitor<'a, 'ast> {
     path: String,
     source: &'a str,
     findings: Vec<Finding>,
+    model: &'a ProgramModel<'ast>,
 }
 
-impl<'a> AccountVisitor<'a> {
-    fn line_of_span(&self, span: proc_macro2::Span) -> usize {
-        span.start().line
-    }
-
-    fn has_derive_accounts(attrs: &[Attribute]) -> bool {
-        attrs.iter().any(|attr| {
-            if !attr.path().is_ident("derive") {
-                return false;
-            }
-            let mut found = false;
-            let _ = attr.parse_nested_meta(|meta| {
-                if meta.path.is_ident("Accounts") {
-                    found = true;
-                }
-                Ok(())
-            });
-            found
-        })
+impl<'a, 'ast> AccountVisitor<'a, 'ast> {
+    /// The handlers, in any scanned file, that take `accounts` as their
+    /// `Context<T>`.
+    fn handlers_of(&self, accounts: &AccountsStruct) -> Vec<&'a BoundHandler<'ast>> {
+        self.model
+            .declared(&self.path, &accounts.name)
+            .map(|declared| self.model.handlers_of(declared))
+            .unwrap_or_default()
     }
 
-    fn field_has_constraint(attrs: &[Attribute]) -> bool {
-        attrs.iter().any(|attr| {
-            let path = attr.path();
-            path.is_ident("account") || path.is_ident("has_one") || path.is_ident("constraint")
-        })
+    /// ` Used by `deposit`, `withdraw`.` naming the instructions that take
+    /// `accounts`, or nothing when none is known.
+    fn used_by(&self, accounts: &AccountsStruct) -> String {
+        let instructions = self
+            .model
+            .declared(&self.path, &accounts.name)
+            .map(|declared| self.model.instructions_of(declared))
+            .unwrap_or_default();
+        if instructions.is_empty() {
+            return String::new();
+        }
+        format!(" Used by `{}`.", instructions.join("`, `"))
     }
 
     fn is_raw_account_info(ty: &syn::Type) -> bool {
@@ -78,6 +179,419 @@ impl<'a> AccountVisitor<'a> {
         false
     }
 
+    /// Authority-like by name (`authority`, `update_authority`, `admin`, ...)
+    /// or because another field ties itself to it with `has_one`.
+    fn is_authority_like(field: &AccountField, accounts: &AccountsStruct) -> bool {
+        let by_name = AUTHORITY_NAMES
+            .iter()
+            .any(|n| field.name == *n || field.name.ends_with(&format!("_{}", n)));
+        let by_has_one = accounts.fields.iter().any(|other| {
+            other.constraints.iter().any(|c| {
+                c.kind == ConstraintKind::HasOne
+                    && c.value
+                        .as_ref()
+                        .is_some_and(|v| v.to_token_stream().to_string() == field.name)
+            })
+        });
+        by_name || by_has_one
+    }
+
+    /// Whether anything requires `field` to have signed: a `signer`
+    /// constraint, an `is_signer` check in a `constraint = ...`, or an
+    /// `is_signer` check in a handler bound to this struct.
+    fn has_signer_validation(&self, field: &AccountField, accounts: &AccountsStruct) -> bool {
+        let is_signer = format!("{}.is_signer", field.name);
+        if field
+            .constraints
+            .iter()
+            .any(|c| c.kind == ConstraintKind::Signer)
+        {
+            return true;
+        }
+        let in_constraint = accounts.fields.iter().any(|f| {
+            f.constraints.iter().any(|c| {
+                c.kind == ConstraintKind::Constraint
+                    && c.value
+                        .as_ref()
+                        .is_some_and(|v| compact_tokens(v.to_token_stream()).contains(&is_signer))
+            })
+        });
+        in_constraint
+            || self
+                .handlers_of(accounts)
+                .into_iter()
+                .any(|h| h.func.contains_tokens(&is_signer))
+    }
+
+    /// Whether a handler refuses to run on an account that already holds
+    /// state: it looks at an `is_initialized`-style flag, the discriminator,
+    /// or compares a field against its default value.
+    fn guards_initialization(func: &FnContext) -> bool {
+        func.mentions_matching(|t| {
+            let t = t.to_lowercase();
+            t.contains("initialized") || t.contains("discriminator")
+        }) || func.contains_tokens("Pubkey::default()")
+            || func.contains_tokens("Default::default()")
+    }
+
+    fn check_reinit(&mut self, accounts: &AccountsStruct) {
+        let handlers: Vec<&BoundHandler> = self.handlers_of(accounts);
+
+        for field in &accounts.fields {
+            // `init_if_needed` happily loads an existing account
+            if field.has(ConstraintKind::InitIfNeeded) {
+                let unguarded: Vec<&str> = handlers
+                    .iter()
+                    .filter(|h| !Self::guards_initialization(&h.func))
+                    .map(|h| h.func.simple_name())
+                    .collect();
+                if !unguarded.is_empty() || handlers.is_empty() {
+                    self.findings.push(Finding {
+                        severity: Severity::High,
+                        check: "reinit".into(),
+                        message: format!(
+                            "Field `{}` in `{}` uses `init_if_needed`, but {} never checks \
+                             whether the account was already initialized. A caller can re-run \
+                             the instruction to reset its state. Prefer `init`, or guard on an \
+                             `is_initialized` flag before writing.",
+                            field.name,
+                            accounts.name,
+                            if unguarded.is_empty() {
+                                "no handler".to_string()
+                            } else {
+                                format!("`{}`", unguarded.join("`, `"))
+                            }
+                        ),
+                        file: self.path.clone(),
+                        line: field.line,
+                        context: None,
+                    });
+                }
+                continue;
+            }
+
+            // Hand-written initialization of an existing `mut` account
+            let created = field.has(ConstraintKind::Init) || field.has(ConstraintKind::Zero);
+            if created || !field.has(ConstraintKind::Mut) || field.type_name() != "Account" {
+                continue;
+            }
+            // Initialization is decided by the `#[program]` instruction, which
+            // may hand the work to `instructions::init::handler` or a helper
+            for handler in handlers.iter().filter(|h| h.is_instruction()) {
+                let name = handler.func.simple_name();
+                if !name.starts_with("init") || Self::guards_initialization(&handler.func) {
+                    continue;
+                }
+                let writes = field_writes(&handler.func, &field.name);
+                if let Some(&line) = writes.first() {
+                    self.findings.push(Finding {
+                        severity: Severity::High,
+                        check: "reinit".into(),
+                        message: format!(
+                            "`{}` writes into `{}` (`mut`, no `init` constraint) without \
+                             checking that it is not already initialized. Anyone can call it \
+                             again to overwrite the account's state. Use `init`, or \
+                             `require!(!{}.is_initialized, ...)` before writing.",
+                            name, field.name, field.name
+                        ),
+                        file: handler.file.clone(),
+                        line,
+                        context: Some(handler.func.name.clone()),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Whether `a` and `b` are required to differ: a `constraint = ...` that
+    /// compares them with `!=`, or a handler that compares their keys.
+    fn has_distinct_check(&self, a: &str, b: &str, accounts: &AccountsStruct) -> bool {
+        let in_constraint = accounts.fields.iter().any(|f| {
+            f.constraints.iter().any(|c| {
+                c.kind == ConstraintKind::Constraint
+                    && c.value.as_ref().is_some_and(|v| {
+                        tokens_contain(v, "!=") && tokens_contain(v, a) && tokens_contain(v, b)
+                    })
+            })
+        });
+        in_constraint
+            || self.handlers_of(accounts).into_iter().any(|h| {
+                let mut guard = PairGuardFinder { a, b, found: false };
+                h.func.visit(&mut guard);
+                guard.found
+            })
+    }
+
+    fn check_duplicate_mutable(&mut self, accounts: &AccountsStruct) {
+        let candidates: Vec<(&AccountField, String)> = accounts
+            .fields
+            .iter()
+            .filter(|f| f.has(ConstraintKind::Mut))
+            .filter(|f| {
+                matches!(
+                    f.type_name().as_str(),
+                    "Account" | "AccountLoader" | "InterfaceAccount"
+                )
+            })
+            .filter_map(|f| Some((f, format!("{}<{}>", f.type_name(), f.inner_type()?))))
+            .collect();
+
+        for (i, (a, ty)) in candidates.iter().enumerate() {
+            for (b, other) in &candidates[i + 1..] {
+                if ty != other || self.has_distinct_check(&a.name, &b.name, accounts) {
+                    continue;
+                }
+                self.findings.push(Finding {
+                    severity: Severity::Medium,
+                    check: "duplicate-mutable-accounts".into(),
+                    message: format!(
+                        "`{}` and `{}` in `{}` are both mutable `{}`, and nothing stops a \
+                         caller from passing the same account for both. Add \
+                         `constraint = {}.key() != {}.key()` or `require_keys_neq!` in the \
+                         handler.",
+                        a.name, b.name, accounts.name, ty, a.name, b.name
+                    ),
+                    file: self.path.clone(),
+                    line: b.line,
+                    context: None,
+                });
+            }
+        }
+    }
+
+    fn is_sysvar_name(name: &str) -> bool {
+        SYSVAR_NAMES.contains(&name) || name.contains("sysvar")
+    }
+
+    /// The `sysvar` module of a sysvar type, e.g. `recent_blockhashes` for
+    /// `RecentBlockhashes`.
+    fn sysvar_module(ty: &str) -> String {
+        let mut module = String::new();
+        for (i, c) in ty.chars().enumerate() {
+            if c.is_ascii_uppercase() && i > 0 {
+                module.push('_');
+            }
+            module.push(c.to_ascii_lowercase());
+        }
+        module
+    }
+
+    /// Raw accounts used as sysvars: read with `Sysvar::from_account_info`
+    /// in a handler, or named like one. Either is fine when the type is
+    /// `Sysvar<'info, T>`, an `address = ...` constraint pins the account,
+    /// or the handler compares its key.
+    fn check_sysvar_spoofing(&mut self, accounts: &AccountsStruct<'ast>) {
+        let handlers: Vec<&BoundHandler<'ast>> = self.handlers_of(accounts);
+        let unpinned = |field: &AccountField| field.is_raw() && !field.has(ConstraintKind::Address);
+        let mut reported: Vec<String> = Vec::new();
+
+        for handler in &handlers {
+            let mut calls = SysvarCallFinder::default();
+            handler.func.visit(&mut calls);
+            for (line, sysvar, arg) in calls.reads {
+                let Some(field) = accounts.trace_field(&handler.func, arg, TRACE_DEPTH) else {
+                    continue;
+                };
+                if !unpinned(field) || compares_key(&handler.func, &field.name) {
+                    continue;
+                }
+                self.findings.push(Finding {
+                    severity: Severity::High,
+                    check: "sysvar-spoofing".into(),
+                    message: format!(
+                        "`{}::from_account_info` reads `{}` from `{}`, a raw account whose \
+                         address is never checked. A caller can pass a fake `{}` account. \
+                         Use `Sysvar<'info, {}>`, or add `address = sysvar::{}::ID`.",
+                        sysvar,
+                        field.name,
+                        accounts.name,
+                        sysvar,
+                        sysvar,
+                        Self::sysvar_module(&sysvar)
+                    ),
+                    file: handler.file.clone(),
+                    line,
+                    context: Some(handler.func.name.clone()),
+                });
+                reported.push(field.name.clone());
+            }
+        }
+
+        for field in &accounts.fields {
+            if !Self::is_sysvar_name(&field.name)
+                || !unpinned(field)
+                || reported.contains(&field.name)
+                || handlers.iter().any(|h| compares_key(&h.func, &field.name))
+            {
+                continue;
+            }
+            self.findings.push(Finding {
+                severity: Severity::High,
+                check: "sysvar-spoofing".into(),
+                message: format!(
+                    "Sysvar field `{}` in `{}` is a raw `{}` with no `address = ...` \
+                     constraint, so any account can be passed in its place. Use \
+                     `Sysvar<'info, T>` or pin the address to the sysvar id.",
+                    field.name,
+                    accounts.name,
+                    field.type_name()
+                ),
+                file: self.path.clone(),
+                line: field.line,
+                context: None,
+            });
+        }
+    }
+
+    /// Whether another field of the struct pins `field`, e.g.
+    /// `has_one = vault_ata` on a state account, `token::mint = mint` on a
+    /// token account, or a `constraint` comparing it. A mint or token
+    /// account stored in validated state is pinned by that reference.
+    fn is_referenced(field: &AccountField, accounts: &AccountsStruct) -> bool {
+        accounts
+            .fields
+            .iter()
+            .filter(|f| f.name != field.name)
+            .flat_map(|f| &f.constraints)
+            .any(|c| {
+                let pins = match c.kind {
+                    ConstraintKind::HasOne
+                    | ConstraintKind::Address
+                    | ConstraintKind::TokenMint
+                    | ConstraintKind::TokenAuthority
+                    | ConstraintKind::AssociatedTokenMint
+                    | ConstraintKind::AssociatedTokenAuthority => true,
+                    ConstraintKind::Constraint => c.value.as_ref().is_some_and(is_comparison),
+                    _ => false,
+                };
+                pins && c
+                    .value
+                    .as_ref()
+                    .is_some_and(|v| tokens_contain(v, &field.name))
+            })
+    }
+
+    fn check_token_accounts(&mut self, accounts: &AccountsStruct) {
+        for field in &accounts.fields {
+            if !matches!(field.type_name().as_str(), "Account" | "InterfaceAccount") {
+                continue;
+            }
+            let Some(inner) = field.inner_type() else {
+                continue;
+            };
+            let kind = inner.rsplit("::").next().unwrap_or(&inner);
+            if kind != "TokenAccount" && kind != "Mint" {
+                continue;
+            }
+
+            let pinned = field.constraints.iter().any(|c| {
+                matches!(
+                    c.kind,
+                    ConstraintKind::Init
+                        | ConstraintKind::Seeds
+                        | ConstraintKind::Address
+                        | ConstraintKind::HasOne
+                        | ConstraintKind::TokenMint
+                        | ConstraintKind::TokenAuthority
+                        | ConstraintKind::AssociatedTokenMint
+                        | ConstraintKind::AssociatedTokenAuthority
+                ) || c.key == "mint::authority"
+            });
+            // `constraint = ata.mint == mint.key()`, here or in a handler
+            let by_field = |f: &str| {
+                let tokens = format!("{}.{}", field.name, f);
+                accounts
+                    .fields
+                    .iter()
+                    .flat_map(|f| &f.constraints)
+                    .any(|c| {
+                        c.kind == ConstraintKind::Constraint
+                            && c.value
+                                .as_ref()
+                                .is_some_and(|v| is_comparison(v) && tokens_contain(v, &tokens))
+                    })
+                    || self
+                        .handlers_of(accounts)
+                        .into_iter()
+                        .any(|h| compares(&h.func, &tokens))
+            };
+            let checked = if kind == "TokenAccount" {
+                by_field("mint") || by_field("owner")
+            } else {
+                by_field("mint_authority")
+                    || self
+                        .handlers_of(accounts)
+                        .into_iter()
+                        .any(|h| compares_key(&h.func, &field.name))
+            };
+            if pinned || checked || Self::is_referenced(field, accounts) {
+                continue;
+            }
+
+            let (severity, message) = if kind == "TokenAccount" {
+                (
+                    Severity::High,
+                    format!(
+                        "Token account `{}` in `{}` is not tied to a mint or owner. An attacker \
+                         can pass their own token account for the same or a worthless mint. Add \
+                         `token::mint = ...` and `token::authority = ...` (or \
+                         `associated_token::*`), `has_one = mint`, or a `constraint` on \
+                         `{}.mint` / `{}.owner`.",
+                        field.name, accounts.name, field.name, field.name
+                    ),
+                )
+            } else {
+                (
+                    Severity::Medium,
+                    format!(
+                        "Mint `{}` in `{}` is not pinned: no `address`, `seeds` or \
+                         `mint::authority` constraint, and no other account refers to it via \
+                         `has_one` or `token::mint`. Any mint can be passed in.",
+                        field.name, accounts.name
+                    ),
+                )
+            };
+            self.findings.push(Finding {
+                severity,
+                check: "token-account-unvalidated".into(),
+                message,
+                file: self.path.clone(),
+                line: field.line,
+                context: None,
+            });
+        }
+    }
+
+    fn check_missing_signer(&mut self, accounts: &AccountsStruct) {
+        for field in &accounts.fields {
+            let ty = field.type_name();
+            if !matches!(
+                ty.as_str(),
+                "AccountInfo" | "UncheckedAccount" | "SystemAccount"
+            ) {
+                continue;
+            }
+            if !Self::is_authority_like(field, accounts)
+                || self.has_signer_validation(field, accounts)
+            {
+                continue;
+            }
+            self.findings.push(Finding {
+                severity: Severity::High,
+                check: "missing-signer".into(),
+                message: format!(
+                    "Authority-like field `{}` in `{}` is `{}` and nothing requires it to \
+                     sign. Anyone can pass an arbitrary account here. Use `Signer<'info>`, \
+                     add a `signer` constraint, or check `is_signer` in the handler.",
+                    field.name, accounts.name, ty
+                ),
+                file: self.path.clone(),
+                line: field.line,
+                context: None,
+            });
+        }
+    }
+
     fn has_check_comment(&self, line: usize) -> bool {
         if line == 0 {
             return false;
@@ -100,73 +614,726 @@ impl<'a> AccountVisitor<'a> {
     }
 }
 
-impl<'a, 'ast> Visit<'ast> for AccountVisitor<'a> {
+impl<'a, 'ast> Visit<'ast> for AccountVisitor<'a, 'ast> {
     fn visit_item_struct(&mut self, node: &'ast ItemStruct) {
-        if !Self::has_derive_accounts(&node.attrs) {
+        let Some(accounts) = AccountsStruct::from_item(node) else {
             return;
+        };
+
+        let used_by = self.used_by(&accounts);
+        for field in &accounts.fields {
+            let line = field.line;
+
+            // Check 1: Raw AccountInfo without CHECK comment
+            if Self::is_raw_account_info(field.ty) && !self.has_check_comment(line) {
+                self.findings.push(Finding {
+                    severity: Severity::High,
+                    check: "unchecked-account".into(),
+                    message: format!(
+                        "Raw `AccountInfo` field `{}` in `{}` without `/// CHECK:` comment.{} \
+                         Use `Account<'info, T>` for type-safe deserialization, or add a \
+                         `/// CHECK:` comment explaining why this is safe.",
+                        field.name, accounts.name, used_by
+                    ),
+                    file: self.path.clone(),
+                    line,
+                    context: None,
+                });
+            }
+
+            // Check 2: Missing constraints on non-trivial account fields
+            // Skip signers and program types — they don't need constraints
+            if Self::is_signer_type(field.ty) || Self::is_program_type(field.ty) {
+                continue;
+            }
+
+            if Self::is_raw_account_info(field.ty) {
+                continue; // Already flagged above
+            }
+
+            // Only fields that carry `#[account(...)]` are checked; `mut` alone
+            // or `payer`/`space` do not validate anything.
+            if field.has_account_attr && !field.is_validated() {
+                self.findings.push(Finding {
+                    severity: Severity::Medium,
+                    check: "missing-constraint".into(),
+                    message: format!(
+                        "Field `{}` in `{}` has `{}` without a validating constraint.{} \
+                         Consider adding `has_one`, `constraint`, `seeds`, or `address` \
+                         to validate this account.",
+                        field.name,
+                        accounts.name,
+                        field.account_attr(),
+                        used_by
+                    ),
+                    file: self.path.clone(),
+                    line,
+                    context: None,
+                });
+            }
         }
 
-        if let syn::Fields::Named(ref fields) = node.fields {
-            for field in &fields.named {
-                let field_name = field
-                    .ident
-                    .as_ref()
-                    .map(|i| i.to_string())
-                    .unwrap_or_default();
-                let line = self.line_of_span(field.ident.as_ref().unwrap().span());
-
-                // Check 1: Raw AccountInfo without CHECK comment
-                if Self::is_raw_account_info(&field.ty) {
-                    if !self.has_check_comment(line) {
-                        self.findings.push(Finding {
-                            severity: Severity::High,
-                            check: "unchecked-account".into(),
-                            message: format!(
-                                "Raw `AccountInfo` field `{}` in `{}` without `/// CHECK:` comment. \
-                                 Use `Account<'info, T>` for type-safe deserialization, or add a \
-                                 `/// CHECK:` comment explaining why this is safe.",
-                                field_name, node.ident
-                            ),
-                            file: self.path.clone(),
-                            line,
-                        });
+        self.check_missing_signer(&accounts);
+        self.check_reinit(&accounts);
+        self.check_duplicate_mutable(&accounts);
+        self.check_sysvar_spoofing(&accounts);
+        self.check_token_accounts(&accounts);
+
+        syn::visit::visit_item_struct(self, node);
+    }
+}
+
+/// Sysvar reads in a body: `Clock::from_account_info(arg)` with the sysvar
+/// name and argument, and the lines of unchecked `load_instruction_at` calls.
+#[derive(Default)]
+struct SysvarCallFinder<'ast> {
+    reads: Vec<(usize, String, &'ast Expr)>,
+    unchecked_loads: Vec<usize>,
+}
+
+impl<'ast> Visit<'ast> for SysvarCallFinder<'ast> {
+    fn visit_expr_call(&mut self, node: &'ast ExprCall) {
+        if let Expr::Path(p) = &*node.func {
+            let segments: Vec<String> = p
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            match segments.as_slice() {
+                [.., ty, ctor]
+                    if ctor == "from_account_info" && SYSVAR_TYPES.contains(&ty.as_str()) =>
+                {
+                    if let Some(arg) = node.args.first() {
+                        self.reads
+                            .push((node.func.span().start().line, ty.clone(), arg));
                     }
                 }
-
-                // Check 2: Missing constraints on non-trivial account fields
-                // Skip signers and program types — they don't need constraints
-                if Self::is_signer_type(&field.ty) || Self::is_program_type(&field.ty) {
-                    continue;
+                [.., name] if name == "load_instruction_at" => {
+                    self.unchecked_loads.push(node.func.span().start().line);
                 }
+                _ => {}
+            }
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+}
 
-                if Self::is_raw_account_info(&field.ty) {
-                    continue; // Already flagged above
-                }
-
-                if !Self::field_has_constraint(&field.attrs) {
-                    // Check if the #[account] attribute exists but is empty vs missing entirely
-                    let has_any_account_attr =
-                        field.attrs.iter().any(|a| a.path().is_ident("account"));
-
-                    if has_any_account_attr {
-                        // Has #[account] but no constraints inside it
-                        self.findings.push(Finding {
-                            severity: Severity::Medium,
-                            check: "missing-constraint".into(),
-                            message: format!(
-                                "Field `{}` in `{}` has `#[account]` without constraints. \
-                                 Consider adding `has_one`, `constraint`, `seeds`, or `address` \
-                                 to validate this account.",
-                                field_name, node.ident
-                            ),
-                            file: self.path.clone(),
-                            line,
-                        });
-                    }
+/// Looks for a comparison of two accounts in a handler: `require_keys_neq!`,
+/// a `require!`-style macro with `!=`, or an `==` / `!=` between them.
+struct PairGuardFinder<'n> {
+    a: &'n str,
+    b: &'n str,
+    found: bool,
+}
+
+impl<'n> PairGuardFinder<'n> {
+    fn mentions_both(&self, node: &impl ToTokens) -> bool {
+        tokens_contain(node, self.a) && tokens_contain(node, self.b)
+    }
+}
+
+impl<'n, 'ast> Visit<'ast> for PairGuardFinder<'n> {
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        let neq = node.path.is_ident("require_keys_neq") || tokens_contain(&node.tokens, "!=");
+        if neq && self.mentions_both(&node.tokens) {
+            self.found = true;
+        }
+        syn::visit::visit_macro(self, node);
+    }
+
+    fn visit_expr_binary(&mut self, node: &'ast ExprBinary) {
+        if matches!(node.op, BinOp::Eq(_) | BinOp::Ne(_)) && self.mentions_both(node) {
+            self.found = true;
+        }
+        syn::visit::visit_expr_binary(self, node);
+    }
+}
+
+/// Finds raw deserialization of `#[account]` types from an account's data,
+/// which skips the discriminator check that `Account<'info, T>` and
+/// `AccountDeserialize::try_deserialize` perform.
+struct CosplayVisitor<'v, 'ast> {
+    path: String,
+    data_accounts: &'v [String],
+    findings: Vec<Finding>,
+    scope: Scope<'ast>,
+    /// The type of the `let` whose initializer is being visited, if any.
+    annotation: Option<&'ast Type>,
+}
+
+impl<'v, 'ast> CosplayVisitor<'v, 'ast> {
+    /// The `#[account]` type a call deserializes into: named by the path
+    /// (`Vault::try_from_slice`, `<Vault as BorshDeserialize>::deserialize`,
+    /// `bytemuck::from_bytes::<Vault>`), else by the `let` it initializes,
+    /// else by the function's return type when the call is what it returns.
+    fn target_type(&self, call: &ExprCall) -> Option<String> {
+        let Expr::Path(p) = &*call.func else {
+            return None;
+        };
+        let last = p.path.segments.last()?;
+        if !RAW_DESERIALIZERS.contains(&last.ident.to_string().as_str()) {
+            return None;
+        }
+
+        let mut candidates: Vec<String> = p
+            .path
+            .segments
+            .iter()
+            .map(|s| s.ident.to_string())
+            .collect();
+        if let Some(qself) = &p.qself {
+            candidates.push(qself.ty.to_token_stream().to_string());
+        }
+        if let syn::PathArguments::AngleBracketed(args) = &last.arguments {
+            candidates.extend(args.args.iter().map(|a| a.to_token_stream().to_string()));
+        }
+        let named = |ty: &Type| -> Option<String> {
+            flatten(ty.to_token_stream())
+                .into_iter()
+                .find(|t| self.data_accounts.contains(t))
+        };
+        candidates
+            .into_iter()
+            .find(|c| self.data_accounts.contains(c))
+            .or_else(|| self.annotation.and_then(&named))
+            .or_else(|| self.returned_type(call).and_then(&named))
+    }
+
+    /// The return type of the current function, if `call` is its tail
+    /// expression or inside one.
+    fn returned_type(&self, call: &ExprCall) -> Option<&'ast Type> {
+        let func = self.scope.current_fn()?;
+        let syn::ReturnType::Type(_, ty) = &func.sig?.output else {
+            return None;
+        };
+        let FnBody::Block(body) = func.body else {
+            return None;
+        };
+        let Some(syn::Stmt::Expr(tail, None)) = body.stmts.last() else {
+            return None;
+        };
+        let (start, end) = (tail.span().start(), tail.span().end());
+        let at = call.span().start();
+        ((start.line, start.column) <= (at.line, at.column)
+            && (at.line, at.column) <= (end.line, end.column))
+            .then_some(&**ty)
+    }
+}
+
+/// Where bytes come from when they are an account's data: the accounts
+/// they are read from (`vault` in `vault.try_borrow_data()`) and the locals
+/// that carry them.
+#[derive(Default)]
+struct DataSource {
+    accounts: Vec<String>,
+    locals: Vec<String>,
+}
+
+impl DataSource {
+    /// Trace `node` back through the locals of `func` to `.data`,
+    /// `try_borrow_data` or `try_borrow_mut_data` of an account.
+    fn of(func: &FnContext, node: &impl ToTokens, depth: usize) -> Option<Self> {
+        let tokens = flatten(node.to_token_stream());
+        let mut source = DataSource::default();
+        for (i, t) in tokens.iter().enumerate() {
+            let after_dot = i > 0 && tokens[i - 1] == ".";
+            if after_dot
+                && matches!(
+                    t.as_str(),
+                    "data" | "try_borrow_data" | "try_borrow_mut_data"
+                )
+            {
+                if let Some(account) = i.checked_sub(2).map(|j| &tokens[j]) {
+                    source.accounts.push(account.clone());
+                }
+            } else if !after_dot && depth > 0 && !source.locals.contains(t) {
+                let inner = func
+                    .local_init(t)
+                    .and_then(|init| Self::of(func, init, depth - 1));
+                if let Some(inner) = inner {
+                    source.locals.push(t.clone());
+                    source.accounts.extend(inner.accounts);
+                    source.locals.extend(inner.locals);
                 }
             }
         }
+        (!source.accounts.is_empty()).then_some(source)
+    }
 
-        syn::visit::visit_item_struct(self, node);
+    fn shares(&self, other: &DataSource) -> bool {
+        self.accounts.iter().any(|a| other.accounts.contains(a))
+            || self.locals.iter().any(|l| other.locals.contains(l))
+    }
+}
+
+/// Finds a comparison against a discriminator (`T::DISCRIMINATOR` or a local
+/// constant) that reads the same account data as `source`.
+struct DiscriminatorCheck<'f, 'ast> {
+    func: &'f FnContext<'ast>,
+    source: &'f DataSource,
+    found: bool,
+}
+
+impl<'f, 'ast> DiscriminatorCheck<'f, 'ast> {
+    fn record(&mut self, node: &impl ToTokens) {
+        let mentions = flatten(node.to_token_stream())
+            .iter()
+            .any(|t| t.to_lowercase().contains("discriminator"));
+        if mentions
+            && DataSource::of(self.func, node, TRACE_DEPTH).is_some_and(|s| s.shares(self.source))
+        {
+            self.found = true;
+        }
+    }
+}
+
+impl<'f, 'ast> Visit<'ast> for DiscriminatorCheck<'f, 'ast> {
+    fn visit_expr_binary(&mut self, node: &'ast ExprBinary) {
+        if matches!(node.op, BinOp::Eq(_) | BinOp::Ne(_)) {
+            self.record(node);
+        }
+        syn::visit::visit_expr_binary(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        if matches!(
+            node.method.to_string().as_str(),
+            "eq" | "ne" | "starts_with"
+        ) {
+            self.record(node);
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        if compares_in_macro(node) {
+            self.record(&node.tokens);
+        }
+        syn::visit::visit_macro(self, node);
+    }
+}
+
+impl<'v, 'ast> Visit<'ast> for CosplayVisitor<'v, 'ast> {
+    track_scope!();
+
+    fn visit_local(&mut self, node: &'ast syn::Local) {
+        let annotation = match &node.pat {
+            syn::Pat::Type(pt) => Some(&*pt.ty),
+            _ => None,
+        };
+        let outer = std::mem::replace(&mut self.annotation, annotation);
+        syn::visit::visit_local(self, node);
+        self.annotation = outer;
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast ExprCall) {
+        let target = self.target_type(node);
+        let func = self.scope.current_fn();
+        // Only bytes read from an account can be another account type
+        let source = func.zip(node.args.first()).and_then(|(func, arg)| {
+            DataSource::of(func, arg, TRACE_DEPTH).map(|source| (func, source))
+        });
+        if let (Some(ty), Some((func, source))) = (target, source) {
+            let mut check = DiscriminatorCheck {
+                func,
+                source: &source,
+                found: false,
+            };
+            func.visit(&mut check);
+            if !check.found {
+                self.findings.push(Finding {
+                    severity: Severity::High,
+                    check: "type-cosplay".into(),
+                    message: format!(
+                        "`{}` is an `#[account]` type but is deserialized from raw bytes \
+                         without checking its 8-byte discriminator. Another account type with \
+                         a compatible layout can be passed in its place. Use \
+                         `Account<'info, {}>`, `{}::try_deserialize`, or compare the data \
+                         against `{}::DISCRIMINATOR` first.",
+                        ty, ty, ty, ty
+                    ),
+                    file: self.path.clone(),
+                    line: node.func.span().start().line,
+                    context: self.scope.qualified_fn(),
+                });
+            }
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::check_source;
+
+    #[test]
+    fn missing_constraint_on_mut_account() {
+        let findings = check_source(
+            r#"
+            #[derive(Accounts)]
+            pub struct Update<'info> {
+                #[account(mut)]
+                pub config: Account<'info, Config>,
+                pub authority: Signer<'info>,
+            }
+            "#,
+            "missing-constraint",
+        );
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains("`config`"));
+    }
+
+    #[test]
+    fn has_one_satisfies_missing_constraint() {
+        let findings = check_source(
+            r#"
+            #[derive(Accounts)]
+            pub struct Update<'info> {
+                #[account(mut, has_one = authority)]
+                pub config: Account<'info, Config>,
+                pub authority: Signer<'info>,
+            }
+            "#,
+            "missing-constraint",
+        );
+        assert!(findings.is_empty());
+    }
+
+    #[test]
+    fn unpinned_token_accounts() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn deposit(ctx: Context<Deposit>) -> Result<()> {
+                    msg!("mint {}", ctx.accounts.vault_token.mint);
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Deposit<'info> {
+                #[account(mut, rent_exempt = enforce)]
+                pub vault_token: Account<'info, TokenAccount>,
+                #[account(mint::decimals = 6)]
+                pub mint: Account<'info, Mint>,
+                #[account(seeds = [mint.key().as_ref()], bump)]
+                pub state: Account<'info, State>,
+            }
+            "#,
+            "token-account-unvalidated",
+        );
+        assert_eq!(findings.len(), 2, "{findings:?}");
+        assert!(findings[0].message.contains("`vault_token`"));
+        assert!(findings[1].message.contains("`mint`"));
+    }
+
+    #[test]
+    fn pinned_token_accounts() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn deposit(ctx: Context<Deposit>) -> Result<()> {
+                    require_keys_eq!(ctx.accounts.user_token.owner, ctx.accounts.user.key());
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Deposit<'info> {
+                #[account(mut, token::mint = mint, token::authority = vault)]
+                pub vault_token: Account<'info, TokenAccount>,
+                #[account(mut, constraint = user_token.mint == mint.key())]
+                pub user_token: Account<'info, TokenAccount>,
+                #[account(has_one = mint)]
+                pub vault: Account<'info, Vault>,
+                pub mint: Account<'info, Mint>,
+                pub user: Signer<'info>,
+            }
+            "#,
+            "token-account-unvalidated",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+
+    #[test]
+    fn raw_deserialization_of_account_data() {
+        let findings = check_source(
+            r#"
+            #[account]
+            pub struct Vault {
+                pub authority: Pubkey,
+            }
+
+            pub fn read(ctx: Context<Read>) -> Result<()> {
+                let data = ctx.accounts.vault.try_borrow_data()?;
+                msg!("expecting {:?}", Vault::DISCRIMINATOR);
+                let vault: Vault = BorshDeserialize::deserialize(&mut &data[8..])?;
+                Ok(())
+            }
+
+            fn load(acc: &AccountInfo, other: &AccountInfo) -> Result<Vault> {
+                let data = acc.try_borrow_data()?;
+                require!(other.try_borrow_data()?[..8] == Vault::DISCRIMINATOR, ErrorCode::Kind);
+                Ok(BorshDeserialize::deserialize(&mut &data[8..])?)
+            }
+            "#,
+            "type-cosplay",
+        );
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [10, 17]);
+    }
+
+    #[test]
+    fn discriminator_checked_or_not_account_data() {
+        let findings = check_source(
+            r#"
+            #[account]
+            pub struct Vault {
+                pub authority: Pubkey,
+            }
+
+            pub fn read(ctx: Context<Read>) -> Result<()> {
+                let data = ctx.accounts.vault.try_borrow_data()?;
+                require!(data[..8] == Vault::DISCRIMINATOR, ErrorCode::Kind);
+                let vault = Vault::try_from_slice(&data[8..])?;
+                Ok(())
+            }
+
+            pub fn decode(ctx: Context<Read>, payload: Vec<u8>) -> Result<()> {
+                let vault = Vault::try_from_slice(&payload)?;
+                Ok(())
+            }
+            "#,
+            "type-cosplay",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+
+    #[test]
+    fn sysvar_read_from_unpinned_account() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod timer {
+                pub fn tick(ctx: Context<Tick>) -> Result<()> {
+                    let info = &ctx.accounts.clock;
+                    let clock = Clock::from_account_info(info)?;
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Tick<'info> {
+                pub clock: AccountInfo<'info>,
+            }
+            "#,
+            "sysvar-spoofing",
+        );
+        assert_eq!(findings.len(), 1, "{findings:?}");
+        assert_eq!(findings[0].line, 6);
+    }
+
+    #[test]
+    fn sysvar_pinned_by_address() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod timer {
+                pub fn tick(ctx: Context<Tick>) -> Result<()> {
+                    let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Tick<'info> {
+                #[account(address = sysvar::clock::ID)]
+                pub clock: AccountInfo<'info>,
+            }
+            "#,
+            "sysvar-spoofing",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+
+    #[test]
+    fn unsigned_authority() {
+        let findings = check_source(
+            r#"
+            #[derive(Accounts)]
+            pub struct Withdraw<'info> {
+                #[account(mut, has_one = owner)]
+                pub vault: Account<'info, Vault>,
+                /// CHECK: compared by has_one
+                pub owner: AccountInfo<'info>,
+                /// CHECK: only read
+                pub admin_authority: UncheckedAccount<'info>,
+            }
+            "#,
+            "missing-signer",
+        );
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [7, 9], "{findings:?}");
+    }
+
+    #[test]
+    fn authority_required_to_sign() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn withdraw(ctx: Context<Withdraw>) -> Result<()> {
+                    require!(ctx.accounts.admin_authority.is_signer, VaultError::Unauthorized);
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Withdraw<'info> {
+                #[account(mut, has_one = owner)]
+                pub vault: Account<'info, Vault>,
+                pub owner: Signer<'info>,
+                /// CHECK: signature checked in the handler
+                pub admin_authority: UncheckedAccount<'info>,
+                /// CHECK: signer constraint
+                #[account(signer)]
+                pub authority: AccountInfo<'info>,
+            }
+            "#,
+            "missing-signer",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+
+    #[test]
+    fn reinitializable_accounts() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+                    ctx.accounts.vault.owner = ctx.accounts.payer.key();
+                    Ok(())
+                }
+
+                pub fn setup(ctx: Context<Setup>) -> Result<()> {
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Initialize<'info> {
+                #[account(mut)]
+                pub vault: Account<'info, Vault>,
+                pub payer: Signer<'info>,
+            }
+
+            #[derive(Accounts)]
+            pub struct Setup<'info> {
+                #[account(init_if_needed, payer = payer, space = 64)]
+                pub config: Account<'info, Config>,
+                #[account(mut)]
+                pub payer: Signer<'info>,
+            }
+            "#,
+            "reinit",
+        );
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [5, 24], "{findings:?}");
+    }
+
+    #[test]
+    fn initialization_guarded() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+                    require!(!ctx.accounts.vault.is_initialized, VaultError::AlreadyInitialized);
+                    ctx.accounts.vault.owner = ctx.accounts.payer.key();
+                    ctx.accounts.vault.is_initialized = true;
+                    Ok(())
+                }
+
+                pub fn setup(ctx: Context<Setup>) -> Result<()> {
+                    ctx.accounts.config.admin = ctx.accounts.payer.key();
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Initialize<'info> {
+                #[account(mut)]
+                pub vault: Account<'info, Vault>,
+                pub payer: Signer<'info>,
+            }
+
+            #[derive(Accounts)]
+            pub struct Setup<'info> {
+                #[account(init, payer = payer, space = 64)]
+                pub config: Account<'info, Config>,
+                #[account(mut)]
+                pub payer: Signer<'info>,
+            }
+            "#,
+            "reinit",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+
+    #[test]
+    fn aliasable_mutable_accounts() {
+        let findings = check_source(
+            r#"
+            #[derive(Accounts)]
+            pub struct Transfer<'info> {
+                #[account(mut)]
+                pub from: Account<'info, Balance>,
+                #[account(mut)]
+                pub to: Account<'info, Balance>,
+                #[account(mut)]
+                pub config: Account<'info, Config>,
+            }
+            "#,
+            "duplicate-mutable-accounts",
+        );
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [7], "{findings:?}");
+    }
+
+    #[test]
+    fn distinct_mutable_accounts() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod bank {
+                pub fn swap(ctx: Context<Swap>) -> Result<()> {
+                    require_keys_neq!(ctx.accounts.left.key(), ctx.accounts.right.key());
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Transfer<'info> {
+                #[account(mut)]
+                pub from: Account<'info, Balance>,
+                #[account(mut, constraint = from.key() != to.key())]
+                pub to: Account<'info, Balance>,
+            }
+
+            #[derive(Accounts)]
+            pub struct Swap<'info> {
+                #[account(mut)]
+                pub left: Account<'info, Balance>,
+                #[account(mut)]
+                pub right: Account<'info, Balance>,
+            }
+            "#,
+            "duplicate-mutable-accounts",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
     }
 }
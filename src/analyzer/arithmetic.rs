@@ -0,0 +1,136 @@
+use super::context::{compact_tokens, FnContext};
+use super::index::CrateIndex;
+use super::types::{is_constant, is_float, is_integer, FieldTypes, TypeEnv, INT_LITERAL};
+use super::workspace::Program;
+use super::{Finding, Severity};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{BinOp, ExprBinary};
+
+/// Checks for:
+/// 1. `+`, `-`, `*` and their compound assignments on integers inside
+///    `#[program]` functions and the helpers they call
+///
+/// Severity depends on the release profile: with `overflow-checks` off the
+/// arithmetic wraps silently, with it on it aborts the transaction.
+pub fn check_arithmetic(path: &str, index: &CrateIndex, overflow_checks: bool) -> Vec<Finding> {
+    let fields = FieldTypes::from_structs(index.crate_structs(path));
+    let mut findings = Vec::new();
+
+    for func in index.program_fns(path) {
+        let mut visitor = ArithmeticVisitor {
+            path,
+            func,
+            env: TypeEnv::for_fn(&fields, func),
+            overflow_checks,
+            findings: Vec::new(),
+        };
+        func.visit(&mut visitor);
+        findings.extend(visitor.findings);
+    }
+
+    findings
+}
+
+/// Whether the `Cargo.toml` that governs `program` enables `overflow-checks`
+/// for release builds. Profiles only take effect in the workspace root, so
+/// the outermost manifest between the crate and the scanned workspace that
+/// declares `[workspace]` wins; without one the crate's own manifest is
+/// used. Release builds default to `false`.
+pub fn release_overflow_checks(program: &Program) -> bool {
+    let manifests: Vec<toml::Table> = program
+        .root
+        .ancestors()
+        .take_while(|dir| dir.starts_with(&program.workspace))
+        .filter_map(|dir| std::fs::read_to_string(dir.join("Cargo.toml")).ok())
+        .filter_map(|text| text.parse::<toml::Table>().ok())
+        .collect();
+
+    let governing = manifests
+        .iter()
+        .rev()
+        .find(|m| m.contains_key("workspace"))
+        .or(manifests.first());
+
+    governing
+        .and_then(|m| {
+            m.get("profile")?
+                .get("release")?
+                .get("overflow-checks")?
+                .as_bool()
+        })
+        .unwrap_or(false)
+}
+
+struct ArithmeticVisitor<'v, 'ast> {
+    path: &'v str,
+    func: &'v FnContext<'ast>,
+    env: TypeEnv<'v>,
+    overflow_checks: bool,
+    findings: Vec<Finding>,
+}
+
+impl<'v, 'ast> ArithmeticVisitor<'v, 'ast> {
+    fn checked_name(op: &BinOp) -> Option<(&'static str, &'static str)> {
+        match op {
+            BinOp::Add(_) | BinOp::AddAssign(_) => Some(("+", "add")),
+            BinOp::Sub(_) | BinOp::SubAssign(_) => Some(("-", "sub")),
+            BinOp::Mul(_) | BinOp::MulAssign(_) => Some(("*", "mul")),
+            _ => None,
+        }
+    }
+
+    /// The integer type of the operation, if at least one operand has a
+    /// declared integer type and neither is a float.
+    fn integer_type(&self, node: &ExprBinary) -> Option<String> {
+        let left = self.env.type_of(&node.left);
+        let right = self.env.type_of(&node.right);
+        if left.iter().chain(right.iter()).any(|t| is_float(t)) {
+            return None;
+        }
+        left.into_iter()
+            .chain(right)
+            .find(|t| is_integer(t) && t != INT_LITERAL)
+    }
+}
+
+impl<'v, 'ast> Visit<'ast> for ArithmeticVisitor<'v, 'ast> {
+    fn visit_expr_binary(&mut self, node: &'ast ExprBinary) {
+        if let Some((symbol, method)) = Self::checked_name(&node.op) {
+            let constant = is_constant(&node.left) && is_constant(&node.right);
+            if let (false, Some(ty)) = (constant, self.integer_type(node)) {
+                let (severity, consequence) = if self.overflow_checks {
+                    (
+                        Severity::Low,
+                        "`overflow-checks` is on, so overflow aborts the transaction with an \
+                         opaque panic",
+                    )
+                } else {
+                    (
+                        Severity::High,
+                        "`overflow-checks` is off for release builds, so overflow wraps silently",
+                    )
+                };
+                self.findings.push(Finding {
+                    severity,
+                    check: "unchecked-arithmetic".into(),
+                    message: format!(
+                        "Unchecked `{}` on `{}` in `{}`: {}. Use `checked_{}` and return an \
+                         error on `None`, or `saturating_{}` where clamping is intended.",
+                        symbol,
+                        ty,
+                        compact_tokens(node.to_token_stream()),
+                        consequence,
+                        method,
+                        method
+                    ),
+                    file: self.path.to_string(),
+                    line: node.op.span().start().line,
+                    context: Some(self.func.name.clone()),
+                });
+            }
+        }
+        syn::visit::visit_expr_binary(self, node);
+    }
+}
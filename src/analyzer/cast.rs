@@ -0,0 +1,290 @@
+use super::context::{compact_tokens, tokens_contain, FnContext};
+use super::index::CrateIndex;
+use super::types::{is_float, primitive, FieldTypes, TypeEnv, INT_LITERAL};
+use super::{Finding, Severity};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{BinOp, Expr, ExprCast};
+
+/// Methods that move lamports directly.
+const LAMPORT_CALLS: &[&str] = &["add_lamports", "sub_lamports", "set_lamports"];
+
+/// A `(line, column)` range of source.
+type Range = ((usize, usize), (usize, usize));
+
+/// Checks for, inside `#[program]` functions and the helpers they call:
+/// 1. `as` casts to a narrower integer type
+/// 2. `as` casts between signed and unsigned types that cannot hold every
+///    value of the source
+///
+/// The source type is inferred from annotated locals, parameters and struct
+/// fields; casts whose source type is not visible are skipped. A cast whose
+/// value reaches a CPI or a lamport write is High, anything else Medium.
+/// Truncation of `u128` intermediates is left to the precision-loss check.
+pub fn check_casts(path: &str, index: &CrateIndex) -> Vec<Finding> {
+    let fields = FieldTypes::from_structs(index.crate_structs(path));
+    let mut findings = Vec::new();
+
+    for func in index.program_fns(path) {
+        let mut sinks = SinkFinder { sinks: Vec::new() };
+        func.visit(&mut sinks);
+
+        let mut visitor = CastVisitor {
+            path,
+            func,
+            env: TypeEnv::for_fn(&fields, func),
+            sinks: sinks.sinks,
+            binding: None,
+            findings: Vec::new(),
+        };
+        func.visit(&mut visitor);
+        findings.extend(visitor.findings);
+    }
+
+    findings
+}
+
+/// Bit width and signedness of an integer type; `usize`/`isize` are taken
+/// as 64 bits, as on the SBF target.
+fn int_layout(ty: &str) -> Option<(u32, bool)> {
+    let signed = ty.starts_with('i');
+    let width = match &ty[1..] {
+        "size" => 64,
+        bits => bits.parse().ok()?,
+    };
+    Some((width, signed))
+}
+
+/// Why a cast from `from` to `to` can change the value, if it can.
+fn lossy(from: &str, to: &str) -> Option<&'static str> {
+    let (from_width, from_signed) = int_layout(from)?;
+    let (to_width, to_signed) = int_layout(to)?;
+    match (from_signed, to_signed) {
+        (true, false) => Some("negative values wrap around to huge unsigned ones"),
+        (false, true) if to_width <= from_width => {
+            Some("large values wrap around to negative ones")
+        }
+        _ if to_width < from_width => Some("the high bits are silently dropped"),
+        _ => None,
+    }
+}
+
+fn range(node: &impl Spanned) -> Range {
+    let span = node.span();
+    let (start, end) = (span.start(), span.end());
+    ((start.line, start.column), (end.line, end.column))
+}
+
+/// Whether `call` invokes another program: `invoke`/`invoke_signed`, an
+/// instruction builder such as `system_instruction::transfer`, or a CPI
+/// helper whose first argument is a `CpiContext`.
+fn is_cpi_call(call: &syn::ExprCall) -> bool {
+    let Expr::Path(p) = &*call.func else {
+        return false;
+    };
+    let segments: Vec<String> = p
+        .path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect();
+    if matches!(
+        segments.last().map(String::as_str),
+        Some("invoke" | "invoke_signed")
+    ) {
+        return true;
+    }
+    if segments.len() > 1 && segments.iter().any(|s| s.ends_with("instruction")) {
+        return true;
+    }
+    segments.len() > 1
+        && call.args.first().is_some_and(|first| {
+            tokens_contain(first, "CpiContext")
+                || first
+                    .to_token_stream()
+                    .to_string()
+                    .to_lowercase()
+                    .contains("cpi")
+        })
+}
+
+/// Collects the operands that end up in a CPI or a lamport balance: CPI
+/// call arguments, the right-hand side of writes through `lamports`, and
+/// the arguments of `add_lamports`-style calls.
+struct SinkFinder<'ast> {
+    sinks: Vec<(&'ast Expr, &'static str)>,
+}
+
+impl<'ast> Visit<'ast> for SinkFinder<'ast> {
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if is_cpi_call(node) {
+            self.sinks.extend(node.args.iter().map(|a| (a, "a CPI")));
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        if LAMPORT_CALLS.contains(&node.method.to_string().as_str()) {
+            self.sinks
+                .extend(node.args.iter().map(|a| (a, "a lamport write")));
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_assign(&mut self, node: &'ast syn::ExprAssign) {
+        if tokens_contain(&node.left, "lamports") {
+            self.sinks.push((&node.right, "a lamport write"));
+        }
+        syn::visit::visit_expr_assign(self, node);
+    }
+
+    fn visit_expr_binary(&mut self, node: &'ast syn::ExprBinary) {
+        if matches!(node.op, BinOp::AddAssign(_) | BinOp::SubAssign(_))
+            && node.left.to_token_stream().to_string().contains("lamports")
+        {
+            self.sinks.push((&node.right, "a lamport write"));
+        }
+        syn::visit::visit_expr_binary(self, node);
+    }
+}
+
+struct CastVisitor<'v, 'ast> {
+    path: &'v str,
+    func: &'v FnContext<'ast>,
+    env: TypeEnv<'v>,
+    sinks: Vec<(&'ast Expr, &'static str)>,
+    /// The local whose initializer is being visited, if any.
+    binding: Option<String>,
+    findings: Vec<Finding>,
+}
+
+impl<'v, 'ast> CastVisitor<'v, 'ast> {
+    /// The sink the cast reaches, directly or through the local it is bound to.
+    fn sink_for(&self, node: &ExprCast) -> Option<&'static str> {
+        let (start, end) = range(node);
+        if let Some((_, what)) = self.sinks.iter().find(|(sink, _)| {
+            let (sink_start, sink_end) = range(*sink);
+            sink_start <= start && end <= sink_end
+        }) {
+            return Some(what);
+        }
+        let aliases = self.func.aliases(self.binding.as_deref()?);
+        self.sinks
+            .iter()
+            .find(|(sink, _)| aliases.mentioned_by(*sink))
+            .map(|(_, what)| *what)
+    }
+}
+
+impl<'v, 'ast> Visit<'ast> for CastVisitor<'v, 'ast> {
+    fn visit_local(&mut self, node: &'ast syn::Local) {
+        let name = match &node.pat {
+            syn::Pat::Ident(pi) => Some(pi.ident.to_string()),
+            syn::Pat::Type(pt) => match &*pt.pat {
+                syn::Pat::Ident(pi) => Some(pi.ident.to_string()),
+                _ => None,
+            },
+            _ => None,
+        };
+        let outer = std::mem::replace(&mut self.binding, name);
+        syn::visit::visit_local(self, node);
+        self.binding = outer;
+    }
+
+    fn visit_expr_closure(&mut self, node: &'ast syn::ExprClosure) {
+        let outer = self.binding.take();
+        syn::visit::visit_expr_closure(self, node);
+        self.binding = outer;
+    }
+
+    fn visit_expr_cast(&mut self, node: &'ast ExprCast) {
+        let target = primitive(&node.ty).filter(|t| !is_float(t));
+        let source = self
+            .env
+            .type_of(&node.expr)
+            .filter(|t| t != INT_LITERAL && !is_float(t));
+        if let (Some(source), Some(target)) = (source, target) {
+            // `u128` intermediates cast down are reported as precision-loss
+            let reason =
+                lossy(&source, &target).filter(|_| source != "u128" || target.ends_with("128"));
+            if let Some(reason) = reason {
+                let sink = self.sink_for(node);
+                let mut message = format!(
+                    "`{}` casts `{}` to `{}`: {}. Use `{}.try_into()` (or `{}::try_from`) and \
+                     return an error when the value does not fit.",
+                    compact_tokens(node.to_token_stream()),
+                    source,
+                    target,
+                    reason,
+                    compact_tokens(node.expr.to_token_stream()),
+                    target
+                );
+                if let Some(sink) = sink {
+                    message.push_str(&format!(" The result reaches {}.", sink));
+                }
+                self.findings.push(Finding {
+                    severity: if sink.is_some() {
+                        Severity::High
+                    } else {
+                        Severity::Medium
+                    },
+                    check: "lossy-cast".into(),
+                    message,
+                    file: self.path.to_string(),
+                    line: node.as_token.span.start().line,
+                    context: Some(self.func.name.clone()),
+                });
+            }
+        }
+        syn::visit::visit_expr_cast(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::{check_source, Severity};
+
+    #[test]
+    fn lossy_casts() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn withdraw(ctx: Context<Withdraw>, amount: u64, delta: i64) -> Result<()> {
+                    let small = amount as u32;
+                    let payout = delta as u64;
+                    ctx.accounts.vault.sub_lamports(payout)?;
+                    Ok(())
+                }
+            }
+            "#,
+            "lossy-cast",
+        );
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [6, 5], "{findings:?}");
+        assert_eq!(findings[0].severity, Severity::High);
+        assert!(findings[0].message.contains("reaches a lamport write"));
+        assert_eq!(findings[1].severity, Severity::Medium);
+    }
+
+    #[test]
+    fn widening_and_checked_casts() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn withdraw(ctx: Context<Withdraw>, amount: u32, delta: i64) -> Result<()> {
+                    let wide = amount as u64;
+                    let signed = amount as i64;
+                    let payout: u64 = delta.try_into()?;
+                    ctx.accounts.vault.sub_lamports(payout)?;
+                    Ok(())
+                }
+            }
+            "#,
+            "lossy-cast",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+}
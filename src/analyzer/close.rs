@@ -0,0 +1,305 @@
+use super::constraints::{collect_accounts_structs, AccountField, ConstraintKind};
+use super::context::{collect_fns, tokens_contain, touches_lamports, writes_through, Aliases};
+use super::index::CrateIndex;
+use super::program::ProgramModel;
+use super::{Finding, Severity};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{BinOp, Expr, ExprAssign, ExprBinary, ExprMethodCall, File};
+
+/// Calls that wipe or hand back an account's data after its lamports are
+/// drained.
+const CLEARING_CALLS: &[&str] = &["fill", "assign", "realloc", "resize", "close"];
+
+/// Checks for:
+/// 1. Accounts closed by draining their lamports by hand without clearing
+///    their data or writing the closed-account discriminator
+/// 2. `close = ...` constraints on accounts the handler keeps using, or that
+///    send the lamports back to the account being closed
+pub fn check_account_closing(
+    file: &File,
+    path: &str,
+    model: &ProgramModel,
+    index: &CrateIndex,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for func in collect_fns(file, index.module(path)) {
+        let mut drains = DrainFinder { drains: Vec::new() };
+        func.visit(&mut drains);
+
+        for (line, account) in drains.drains {
+            // The Anchor discriminator closes the account for good, whatever
+            // else the function does
+            if func.mentions("CLOSED_ACCOUNT_DISCRIMINATOR") {
+                continue;
+            }
+            let aliases = func.aliases(&account);
+            let mut clearing = ClearingFinder {
+                aliases: &aliases,
+                found: false,
+            };
+            func.visit(&mut clearing);
+            if clearing.found {
+                continue;
+            }
+            findings.push(Finding {
+                severity: Severity::High,
+                check: "insecure-close".into(),
+                message: format!(
+                    "`{}` drains the lamports of `{}` without clearing its data or writing the \
+                     closed-account discriminator. Within the same transaction the account can \
+                     be refunded and revived with its old state. Use Anchor's \
+                     `#[account(mut, close = <destination>)]` constraint instead.",
+                    func.simple_name(),
+                    account
+                ),
+                file: path.to_string(),
+                line,
+                context: Some(func.name.clone()),
+            });
+        }
+    }
+
+    for accounts in collect_accounts_structs(file) {
+        for field in &accounts.fields {
+            let Some(destination) = close_destination(field) else {
+                continue;
+            };
+            if destination == field.name {
+                findings.push(Finding {
+                    severity: Severity::Medium,
+                    check: "insecure-close".into(),
+                    message: format!(
+                        "`{}` in `{}` is closed into itself (`close = {}`). The lamports never \
+                         leave the account, so it is not closed. Point `close` at the \
+                         recipient account.",
+                        field.name, accounts.name, destination
+                    ),
+                    file: path.to_string(),
+                    line: field.line,
+                    context: None,
+                });
+            }
+        }
+    }
+
+    for (handler, accounts) in model.handlers_in(path) {
+        for field in &accounts.fields {
+            let Some(destination) = close_destination(field) else {
+                continue;
+            };
+            if destination == field.name {
+                continue;
+            }
+
+            let writes = writes_through(&handler.func, &handler.func.aliases(&field.name));
+            if let Some(&line) = writes.first() {
+                findings.push(Finding {
+                    severity: Severity::Medium,
+                    check: "insecure-close".into(),
+                    message: format!(
+                        "`{}` writes into `{}`, which `{}` closes with `close = {}`. Anchor \
+                         closes the account after the handler returns, so the write is lost; \
+                         if the account is meant to stay open, drop the `close` constraint.",
+                        handler.func.simple_name(),
+                        field.name,
+                        accounts.name,
+                        destination
+                    ),
+                    file: path.to_string(),
+                    line,
+                    context: Some(handler.func.name.clone()),
+                });
+            }
+        }
+    }
+
+    findings
+}
+
+/// The account a `close = ...` constraint on `field` sends the lamports to.
+fn close_destination(field: &AccountField) -> Option<String> {
+    field
+        .constraints
+        .iter()
+        .find(|c| c.kind == ConstraintKind::Close)
+        .map(|c| {
+            c.value
+                .as_ref()
+                .map(|v| v.to_token_stream().to_string())
+                .unwrap_or_default()
+        })
+}
+
+/// The account whose lamports `expr` reaches, e.g. `vault` for
+/// `ctx.accounts.vault.to_account_info().lamports.borrow_mut()`.
+fn lamports_owner(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Field(f) => match &f.member {
+            syn::Member::Named(name) if name == "lamports" || name == "accounts" => {
+                lamports_owner(&f.base)
+            }
+            syn::Member::Named(name) => Some(name.to_string()),
+            syn::Member::Unnamed(_) => lamports_owner(&f.base),
+        },
+        Expr::MethodCall(m) => lamports_owner(&m.receiver),
+        Expr::Unary(u) => lamports_owner(&u.expr),
+        Expr::Try(t) => lamports_owner(&t.expr),
+        Expr::Paren(p) => lamports_owner(&p.expr),
+        Expr::Reference(r) => lamports_owner(&r.expr),
+        Expr::Path(p) => p.path.get_ident().map(|i| i.to_string()),
+        _ => None,
+    }
+}
+
+fn is_zero(expr: &Expr) -> bool {
+    matches!(expr, Expr::Lit(l) if matches!(&l.lit, syn::Lit::Int(i) if i.base10_digits() == "0"))
+}
+
+/// Collects lamport drains: `**x.lamports.borrow_mut() = 0`,
+/// `**x.try_borrow_mut_lamports()? -= x.lamports()` and `x.set_lamports(0)`.
+struct DrainFinder {
+    drains: Vec<(usize, String)>,
+}
+
+impl<'ast> Visit<'ast> for DrainFinder {
+    fn visit_expr_assign(&mut self, node: &'ast ExprAssign) {
+        if is_zero(&node.right) && touches_lamports(&node.left) {
+            if let Some(account) = lamports_owner(&node.left) {
+                self.drains.push((node.eq_token.span.start().line, account));
+            }
+        }
+        syn::visit::visit_expr_assign(self, node);
+    }
+
+    fn visit_expr_binary(&mut self, node: &'ast ExprBinary) {
+        if matches!(node.op, BinOp::SubAssign(_))
+            && touches_lamports(&node.left)
+            && tokens_contain(&node.right, "lamports()")
+        {
+            if let Some(account) = lamports_owner(&node.left) {
+                // Only a full drain: the amount is the account's own balance
+                if lamports_owner(&node.right).as_deref() == Some(account.as_str()) {
+                    self.drains.push((node.op.span().start().line, account));
+                }
+            }
+        }
+        syn::visit::visit_expr_binary(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        if node.method == "set_lamports" && node.args.first().is_some_and(is_zero) {
+            if let Some(account) = lamports_owner(&node.receiver) {
+                self.drains.push((node.method.span().start().line, account));
+            }
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+}
+
+/// Whether the drained account's data is zeroed, reassigned or resized.
+struct ClearingFinder<'n> {
+    aliases: &'n Aliases,
+    found: bool,
+}
+
+impl<'n, 'ast> Visit<'ast> for ClearingFinder<'n> {
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        let method = node.method.to_string();
+        if CLEARING_CALLS.contains(&method.as_str()) && self.aliases.mentioned_by(&node.receiver) {
+            self.found = true;
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::check_source;
+
+    #[test]
+    fn insecurely_closed_accounts() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn drain(ctx: Context<Drain>) -> Result<()> {
+                    let vault = ctx.accounts.vault.to_account_info();
+                    **ctx.accounts.receiver.lamports.borrow_mut() += vault.lamports();
+                    **vault.lamports.borrow_mut() = 0;
+                    Ok(())
+                }
+
+                pub fn retire(ctx: Context<Retire>) -> Result<()> {
+                    ctx.accounts.vault.balance = 0;
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Drain<'info> {
+                #[account(mut)]
+                pub vault: Account<'info, Vault>,
+                #[account(mut)]
+                pub receiver: SystemAccount<'info>,
+            }
+
+            #[derive(Accounts)]
+            pub struct Retire<'info> {
+                #[account(mut, close = receiver)]
+                pub vault: Account<'info, Vault>,
+                #[account(mut, close = stale)]
+                pub stale: Account<'info, Vault>,
+                #[account(mut)]
+                pub receiver: SystemAccount<'info>,
+            }
+            "#,
+            "insecure-close",
+        );
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [7, 30, 12], "{findings:?}");
+    }
+
+    #[test]
+    fn securely_closed_accounts() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn drain(ctx: Context<Drain>) -> Result<()> {
+                    let vault = ctx.accounts.vault.to_account_info();
+                    **ctx.accounts.receiver.lamports.borrow_mut() += vault.lamports();
+                    **vault.lamports.borrow_mut() = 0;
+                    vault.data.borrow_mut().fill(0);
+                    Ok(())
+                }
+
+                pub fn retire(ctx: Context<Retire>) -> Result<()> {
+                    msg!("closing {}", ctx.accounts.vault.key());
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Drain<'info> {
+                #[account(mut)]
+                pub vault: Account<'info, Vault>,
+                #[account(mut)]
+                pub receiver: SystemAccount<'info>,
+            }
+
+            #[derive(Accounts)]
+            pub struct Retire<'info> {
+                #[account(mut, close = receiver)]
+                pub vault: Account<'info, Vault>,
+                #[account(mut)]
+                pub receiver: SystemAccount<'info>,
+            }
+            "#,
+            "insecure-close",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+}
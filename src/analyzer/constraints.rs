@@ -0,0 +1,434 @@
+use super::context::{compact_tokens, FnContext};
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::visit::Visit;
+use syn::{Attribute, Expr, Field, File, Ident, ItemStruct, Token, Type};
+
+/// The kind of a single `#[account(...)]` entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintKind {
+    Mut,
+    Init,
+    InitIfNeeded,
+    Zero,
+    Signer,
+    Seeds,
+    Bump,
+    HasOne,
+    Constraint,
+    Address,
+    Owner,
+    Close,
+    Realloc,
+    TokenMint,
+    TokenAuthority,
+    AssociatedTokenMint,
+    AssociatedTokenAuthority,
+    Payer,
+    Space,
+    Other,
+}
+
+impl ConstraintKind {
+    fn from_key(key: &str) -> Self {
+        match key {
+            "mut" => Self::Mut,
+            "init" => Self::Init,
+            "init_if_needed" => Self::InitIfNeeded,
+            "zero" => Self::Zero,
+            "signer" => Self::Signer,
+            "seeds" => Self::Seeds,
+            "bump" => Self::Bump,
+            "has_one" => Self::HasOne,
+            "constraint" => Self::Constraint,
+            "address" => Self::Address,
+            "owner" => Self::Owner,
+            "close" => Self::Close,
+            "realloc" => Self::Realloc,
+            "token::mint" => Self::TokenMint,
+            "token::authority" => Self::TokenAuthority,
+            "associated_token::mint" => Self::AssociatedTokenMint,
+            "associated_token::authority" => Self::AssociatedTokenAuthority,
+            "payer" => Self::Payer,
+            "space" => Self::Space,
+            _ => Self::Other,
+        }
+    }
+}
+
+/// One parsed entry of an `#[account(...)]` attribute, e.g. `mut`,
+/// `has_one = authority @ ErrorCode::Unauthorized` or `seeds = [b"vault"]`.
+pub struct Constraint {
+    pub kind: ConstraintKind,
+    /// The key as written, e.g. `token::mint` or `realloc::payer`.
+    pub key: String,
+    pub value: Option<Expr>,
+}
+
+impl Constraint {
+    /// Whether this entry actually validates the account it is attached
+    /// to. `mut`, `payer`, `space` and friends only describe what Anchor
+    /// should do with the account. Unknown keys are assumed to validate so
+    /// that new Anchor constraints do not turn into noise.
+    pub fn validates(&self) -> bool {
+        !matches!(
+            self.kind,
+            ConstraintKind::Mut
+                | ConstraintKind::Bump
+                | ConstraintKind::Close
+                | ConstraintKind::Realloc
+                | ConstraintKind::Payer
+                | ConstraintKind::Space
+        ) && !self.key.starts_with("realloc::")
+            && self.key != "rent_exempt"
+    }
+}
+
+impl std::fmt::Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(
+                f,
+                "{} = {}",
+                self.key,
+                compact_tokens(value.to_token_stream())
+            ),
+            None => write!(f, "{}", self.key),
+        }
+    }
+}
+
+impl Parse for Constraint {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut key = Ident::parse_any(input)?.to_string();
+        while input.peek(Token![::]) {
+            input.parse::<Token![::]>()?;
+            key.push_str("::");
+            key.push_str(&Ident::parse_any(input)?.to_string());
+        }
+
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            Some(input.parse::<Expr>()?)
+        } else {
+            None
+        };
+
+        // Custom error: `has_one = authority @ ErrorCode::Unauthorized`
+        if input.peek(Token![@]) {
+            input.parse::<Token![@]>()?;
+            input.parse::<Expr>()?;
+        }
+
+        Ok(Constraint {
+            kind: ConstraintKind::from_key(&key),
+            key,
+            value,
+        })
+    }
+}
+
+/// A field of a `#[derive(Accounts)]` struct together with its parsed
+/// `#[account(...)]` constraints.
+pub struct AccountField<'ast> {
+    pub name: String,
+    pub ty: &'ast Type,
+    pub line: usize,
+    /// Whether the field carries any `#[account]` attribute, even an empty one.
+    pub has_account_attr: bool,
+    pub constraints: Vec<Constraint>,
+}
+
+impl<'ast> AccountField<'ast> {
+    fn from_field(field: &'ast Field) -> Option<Self> {
+        let ident = field.ident.as_ref()?;
+        let mut constraints = Vec::new();
+        let mut has_account_attr = false;
+
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("account")) {
+            has_account_attr = true;
+            constraints.extend(parse_account_attr(attr));
+        }
+
+        Some(AccountField {
+            name: ident.to_string(),
+            ty: &field.ty,
+            line: ident.span().start().line,
+            has_account_attr,
+            constraints,
+        })
+    }
+
+    pub fn has(&self, kind: ConstraintKind) -> bool {
+        self.constraints.iter().any(|c| c.kind == kind)
+    }
+
+    /// The account wrapper type, e.g. `Account`, `Signer` or `AccountInfo`.
+    /// `Box<...>` is looked through.
+    pub fn type_name(&self) -> String {
+        outer_type(self.ty)
+            .map(|seg| seg.ident.to_string())
+            .unwrap_or_default()
+    }
+
+    /// The account data type, e.g. `Vault` for `Account<'info, Vault>`:
+    /// the last type argument of the wrapper, rendered compactly.
+    pub fn inner_type(&self) -> Option<String> {
+        let syn::PathArguments::AngleBracketed(args) = &outer_type(self.ty)?.arguments else {
+            return None;
+        };
+        args.args.iter().rev().find_map(|arg| match arg {
+            syn::GenericArgument::Type(ty) => Some(compact_tokens(ty.to_token_stream())),
+            _ => None,
+        })
+    }
+
+    /// Whether the field is a raw `AccountInfo` / `UncheckedAccount` that
+    /// Anchor does not deserialize or owner-check.
+    pub fn is_raw(&self) -> bool {
+        matches!(
+            self.type_name().as_str(),
+            "AccountInfo" | "UncheckedAccount"
+        )
+    }
+
+    pub fn is_validated(&self) -> bool {
+        self.constraints.iter().any(Constraint::validates)
+    }
+
+    /// Render the attribute back as `#[account(mut, close = admin)]`.
+    pub fn account_attr(&self) -> String {
+        let items: Vec<String> = self.constraints.iter().map(|c| c.to_string()).collect();
+        format!("#[account({})]", items.join(", "))
+    }
+}
+
+/// A `#[derive(Accounts)]` struct with its fields modelled.
+pub struct AccountsStruct<'ast> {
+    pub name: String,
+    pub fields: Vec<AccountField<'ast>>,
+    /// Instruction arguments made visible to the constraints with
+    /// `#[instruction(bump: u8, ...)]`, by name.
+    pub instruction_args: Vec<String>,
+}
+
+impl<'ast> AccountsStruct<'ast> {
+    /// The field that `expr` refers to inside `func`, directly
+    /// (`ctx.accounts.token_program.key`, `self.token_program`) or through
+    /// up to `depth` locals bound from it.
+    pub fn trace_field(
+        &self,
+        func: &FnContext<'ast>,
+        expr: &'ast Expr,
+        depth: usize,
+    ) -> Option<&AccountField<'ast>> {
+        let tokens = expr.to_token_stream().to_string();
+        let idents: Vec<&str> = tokens
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .filter(|t| !t.is_empty())
+            .collect();
+        if let Some(field) = idents
+            .iter()
+            .find_map(|i| self.fields.iter().find(|f| f.name == *i))
+        {
+            return Some(field);
+        }
+        if depth == 0 {
+            return None;
+        }
+        idents.iter().find_map(|i| {
+            let init = func.local_init(i)?;
+            self.trace_field(func, init, depth - 1)
+        })
+    }
+
+    /// Returns `None` unless the struct derives `Accounts`.
+    pub fn from_item(node: &'ast ItemStruct) -> Option<Self> {
+        if !has_derive_accounts(&node.attrs) {
+            return None;
+        }
+        let fields = match &node.fields {
+            syn::Fields::Named(named) => named
+                .named
+                .iter()
+                .filter_map(AccountField::from_field)
+                .collect(),
+            _ => Vec::new(),
+        };
+        let instruction_args = node
+            .attrs
+            .iter()
+            .filter(|a| a.path().is_ident("instruction"))
+            .filter_map(|a| a.parse_args_with(parse_instruction_args).ok())
+            .flatten()
+            .collect();
+        Some(AccountsStruct {
+            name: node.ident.to_string(),
+            fields,
+            instruction_args,
+        })
+    }
+}
+
+fn outer_type(ty: &Type) -> Option<&syn::PathSegment> {
+    let Type::Path(tp) = ty else {
+        return None;
+    };
+    let seg = tp.path.segments.last()?;
+    if seg.ident == "Box" {
+        if let syn::PathArguments::AngleBracketed(args) = &seg.arguments {
+            if let Some(syn::GenericArgument::Type(inner)) = args.args.first() {
+                return outer_type(inner);
+            }
+        }
+    }
+    Some(seg)
+}
+
+/// Collect every `#[derive(Accounts)]` struct in `file`.
+pub fn collect_accounts_structs(file: &File) -> Vec<AccountsStruct<'_>> {
+    let mut collector = AccountsCollector(Vec::new());
+    collector.visit_file(file);
+    collector.0
+}
+
+struct AccountsCollector<'ast>(Vec<AccountsStruct<'ast>>);
+
+impl<'ast> Visit<'ast> for AccountsCollector<'ast> {
+    fn visit_item_struct(&mut self, node: &'ast ItemStruct) {
+        if let Some(accounts) = AccountsStruct::from_item(node) {
+            self.0.push(accounts);
+        }
+    }
+}
+
+fn has_derive_accounts(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        if !attr.path().is_ident("derive") {
+            return false;
+        }
+        let mut found = false;
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("Accounts") {
+                found = true;
+            }
+            Ok(())
+        });
+        found
+    })
+}
+
+/// The argument names of `#[instruction(bump: u8, name: String)]`.
+fn parse_instruction_args(input: ParseStream) -> syn::Result<Vec<String>> {
+    let mut names = Vec::new();
+    while !input.is_empty() {
+        let name = Ident::parse_any(input)?;
+        input.parse::<Token![:]>()?;
+        input.parse::<Type>()?;
+        names.push(name.to_string());
+        if input.is_empty() {
+            break;
+        }
+        input.parse::<Token![,]>()?;
+    }
+    Ok(names)
+}
+
+/// Parse the entries of a single `#[account(...)]` attribute. A bare
+/// `#[account]` yields no entries. If the contents cannot be parsed the
+/// attribute is recorded as a single unknown entry, which counts as
+/// validating, so a parser gap never produces a finding on its own.
+fn parse_account_attr(attr: &Attribute) -> Vec<Constraint> {
+    if matches!(attr.meta, syn::Meta::Path(_)) {
+        return Vec::new();
+    }
+    match attr.parse_args_with(Punctuated::<Constraint, Token![,]>::parse_terminated) {
+        Ok(items) => items.into_iter().collect(),
+        Err(_) => vec![Constraint {
+            kind: ConstraintKind::Other,
+            key: "<unparsed>".into(),
+            value: None,
+        }],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Vec<(String, Vec<(ConstraintKind, String)>)> {
+        let file = syn::parse_file(source).expect("test source parses");
+        collect_accounts_structs(&file)
+            .into_iter()
+            .flat_map(|s| s.fields)
+            .map(|f| {
+                let constraints = f
+                    .constraints
+                    .iter()
+                    .map(|c| (c.kind, c.to_string()))
+                    .collect();
+                (f.name, constraints)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_keys_values_and_custom_errors() {
+        let fields = parse(
+            r#"
+            #[derive(Accounts)]
+            #[instruction(bump: u8)]
+            pub struct Withdraw<'info> {
+                #[account(mut, has_one = authority @ VaultError::Unauthorized, seeds = [b"vault"], bump)]
+                pub vault: Account<'info, Vault>,
+                #[account(token::mint = mint, token::authority = vault)]
+                pub vault_tokens: Account<'info, TokenAccount>,
+                pub authority: Signer<'info>,
+            }
+            "#,
+        );
+        assert_eq!(
+            fields[0].1,
+            vec![
+                (ConstraintKind::Mut, "mut".into()),
+                (ConstraintKind::HasOne, "has_one = authority".into()),
+                (ConstraintKind::Seeds, "seeds = [b\"vault\"]".into()),
+                (ConstraintKind::Bump, "bump".into()),
+            ]
+        );
+        assert_eq!(
+            fields[1].1,
+            vec![
+                (ConstraintKind::TokenMint, "token::mint = mint".into()),
+                (
+                    ConstraintKind::TokenAuthority,
+                    "token::authority = vault".into()
+                ),
+            ]
+        );
+        assert!(fields[2].1.is_empty());
+    }
+
+    #[test]
+    fn only_validating_entries_validate() {
+        let file = syn::parse_file(
+            r#"
+            #[derive(Accounts)]
+            pub struct Update<'info> {
+                #[account(mut, realloc = 64, realloc::payer = payer, realloc::zero = false)]
+                pub grown: Account<'info, Data>,
+                #[account(mut, address = CONFIG)]
+                pub pinned: Account<'info, Data>,
+                #[account(mut, !!)]
+                pub unparsed: Account<'info, Data>,
+            }
+            "#,
+        )
+        .unwrap();
+        let structs = collect_accounts_structs(&file);
+        let validated: Vec<bool> = structs[0].fields.iter().map(|f| f.is_validated()).collect();
+        assert_eq!(validated, vec![false, true, true]);
+    }
+}
@@ -0,0 +1,931 @@
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+use quote::ToTokens;
+use std::collections::HashSet;
+use std::rc::Rc;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{
+    BinOp, Block, Expr, File, GenericArgument, ImplItemFn, ItemFn, ItemMod, Macro, PathArguments,
+    Signature, TraitItemFn, Type,
+};
+
+/// The body of a function-like item: a block for `fn`s and an expression
+/// for closures.
+#[derive(Clone, Copy)]
+pub enum FnBody<'ast> {
+    Block(&'ast Block),
+    Expr(&'ast Expr),
+}
+
+/// The function a check is currently looking at.
+///
+/// The body is kept as AST so checks can walk expressions, and is also
+/// flattened into tokens so queries can see inside macro invocations such
+/// as `require!` or `msg!`, which `syn` leaves unparsed.
+///
+/// Contexts taken from the crate index also carry the local functions they
+/// call, so that a validation done in a helper counts for its caller.
+#[derive(Clone)]
+pub struct FnContext<'ast> {
+    /// Fully qualified name, e.g. `vault::Withdraw::transfer`.
+    pub name: String,
+    /// `None` for closures.
+    pub sig: Option<&'ast Signature>,
+    pub body: FnBody<'ast>,
+    /// Whether the function is declared inside the `#[program]` module.
+    pub in_program: bool,
+    /// Calls to local functions, followed up to the configured call depth.
+    pub callees: Vec<Callee<'ast>>,
+    tokens: Vec<String>,
+    /// The tokens of every callee with its parameters replaced by the
+    /// arguments passed for them, one entry per distinct body so that a
+    /// helper reached along many paths is inlined once.
+    inlined: Vec<Vec<String>>,
+}
+
+/// A call to a local function.
+#[derive(Clone)]
+pub struct Callee<'ast> {
+    /// Shared between every call site that reaches it at the same depth.
+    pub func: Rc<FnContext<'ast>>,
+    /// Each parameter of `func` (`self` for the receiver) with the tokens
+    /// of the argument the caller passes for it.
+    pub bindings: Vec<(String, Vec<String>)>,
+    pub line: usize,
+}
+
+impl<'ast> Callee<'ast> {
+    /// The parameters `field` of the caller reaches the callee through:
+    /// those whose argument mentions it, e.g. `acc` for
+    /// `assert_authority(&ctx.accounts.authority, ..)` and `authority`.
+    /// A method called on the accounts struct itself, or a function handed
+    /// the whole `Context`, sees the field under its own name.
+    pub fn params_for<'c>(&'c self, field: &'c str) -> Vec<&'c str> {
+        self.bindings
+            .iter()
+            .filter_map(|(param, arg)| {
+                if arg.iter().any(|t| t == field) {
+                    Some(param.as_str())
+                } else if (param == "self"
+                    && matches!(arg.last().map(String::as_str), Some("accounts" | "self")))
+                    || self.takes_context(param)
+                {
+                    Some(field)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Whether `param` of the callee is a `Context<T>`.
+    fn takes_context(&self, param: &str) -> bool {
+        self.func.sig.is_some_and(|sig| {
+            sig.inputs.iter().any(|input| match input {
+                syn::FnArg::Typed(pt) => {
+                    matches!(&*pt.pat, syn::Pat::Ident(pi) if pi.ident == param)
+                        && matches!(&*pt.ty, Type::Path(tp)
+                            if tp.path.segments.last().is_some_and(|s| s.ident == "Context"))
+                }
+                syn::FnArg::Receiver(_) => false,
+            })
+        })
+    }
+}
+
+impl<'ast> FnContext<'ast> {
+    pub fn new(
+        name: String,
+        sig: Option<&'ast Signature>,
+        body: FnBody<'ast>,
+        in_program: bool,
+    ) -> Self {
+        let tokens = match body {
+            FnBody::Block(b) => flatten(b.to_token_stream()),
+            FnBody::Expr(e) => flatten(e.to_token_stream()),
+        };
+        Self {
+            name,
+            sig,
+            body,
+            in_program,
+            callees: Vec::new(),
+            tokens,
+            inlined: Vec::new(),
+        }
+    }
+
+    /// Attach the functions this one calls.
+    pub fn with_callees(mut self, callees: Vec<Callee<'ast>>) -> Self {
+        self.inlined.clear();
+        let mut seen = HashSet::new();
+        for callee in &callees {
+            for body in std::iter::once(&callee.func.tokens).chain(&callee.func.inlined) {
+                let mut inlined = Vec::with_capacity(body.len());
+                let mut previous: Option<&str> = None;
+                for t in body {
+                    // `acc.key` becomes `ctx.accounts.authority.key`, but a
+                    // field that happens to share a parameter's name stays
+                    let bound = callee
+                        .bindings
+                        .iter()
+                        .find(|(param, _)| param == t && previous != Some("."));
+                    match bound {
+                        Some((_, arg)) => inlined.extend(arg.iter().cloned()),
+                        None => inlined.push(t.clone()),
+                    }
+                    previous = Some(t);
+                }
+                if seen.insert(inlined.clone()) {
+                    self.inlined.push(inlined);
+                }
+            }
+        }
+        self.callees = callees;
+        self
+    }
+
+    /// The unqualified name, e.g. `transfer`.
+    pub fn simple_name(&self) -> &str {
+        self.name.rsplit("::").next().unwrap_or(&self.name)
+    }
+
+    /// Whether the body, or a callee, mentions `ident` anywhere, including
+    /// inside macros.
+    pub fn mentions(&self, ident: &str) -> bool {
+        self.tokens
+            .iter()
+            .chain(self.inlined.iter().flatten())
+            .any(|t| t == ident)
+    }
+
+    /// Whether any identifier in the body or a callee satisfies `pred`.
+    pub fn mentions_matching(&self, pred: impl Fn(&str) -> bool) -> bool {
+        self.tokens
+            .iter()
+            .chain(self.inlined.iter().flatten())
+            .any(|t| t.starts_with(is_ident_start) && pred(t))
+    }
+
+    /// Whether the token sequence of `pattern` appears in the body or a
+    /// callee, e.g. `contains_tokens("crate::ID")` or
+    /// `contains_tokens(".bump")`.
+    pub fn contains_tokens(&self, pattern: &str) -> bool {
+        let Some(needle) = needle(pattern) else {
+            return false;
+        };
+        std::iter::once(&self.tokens)
+            .chain(&self.inlined)
+            .any(|body| contains_needle(body, &needle))
+    }
+
+    /// Walk the body with `visitor`.
+    pub fn visit(&self, visitor: &mut impl Visit<'ast>) {
+        match self.body {
+            FnBody::Block(b) => visitor.visit_block(b),
+            FnBody::Expr(e) => visitor.visit_expr(e),
+        }
+    }
+
+    /// The expression a `let` binding `name` was initialized from, e.g.
+    /// `seeds` in `let seeds = ...;` or `bump` in
+    /// `let (pda, bump) = Pubkey::find_program_address(...);`. When the name
+    /// is bound more than once the last binding wins.
+    pub fn local_init(&self, name: &str) -> Option<&'ast Expr> {
+        let mut finder = LocalInit { name, init: None };
+        self.visit(&mut finder);
+        finder.init
+    }
+
+    /// The locals that carry `root` through the body: every binding (`let`,
+    /// `if let`, `for`) whose initializer mentions `root` or another such
+    /// local. `let data = vault.try_borrow_data()?;` makes `data` an alias of
+    /// `vault`.
+    pub fn aliases(&self, root: &str) -> Aliases {
+        let mut bindings = BindingCollector::default();
+        self.visit(&mut bindings);
+
+        let mut aliases = Aliases {
+            root: root.to_string(),
+            locals: HashSet::new(),
+        };
+        loop {
+            let before = aliases.locals.len();
+            for (pat, init) in &bindings.bindings {
+                if aliases.matches(init) {
+                    aliases.locals.extend(pat.iter().cloned());
+                }
+            }
+            if aliases.locals.len() == before {
+                return aliases;
+            }
+        }
+    }
+}
+
+/// A place written to: the target of a plain or compound assignment
+/// (`vault.admin = ...`, `vault.total += ...`) or the receiver of a
+/// `set_inner` call.
+pub struct WriteSite<'ast> {
+    pub line: usize,
+    pub target: &'ast Expr,
+    /// The compound operator, `None` for `=` and `set_inner`.
+    pub op: Option<BinOp>,
+}
+
+/// Every place `func` writes through, whatever it is bound to.
+pub fn write_sites<'ast>(func: &FnContext<'ast>) -> Vec<WriteSite<'ast>> {
+    let mut finder = WriteFinder { sites: Vec::new() };
+    func.visit(&mut finder);
+    finder.sites
+}
+
+/// Lines where `node` writes into something that `aliases` covers.
+pub fn writes_through(node: &FnContext, aliases: &Aliases) -> Vec<usize> {
+    write_sites(node)
+        .into_iter()
+        .filter(|site| aliases.mentioned_by(site.target))
+        .map(|site| site.line)
+        .collect()
+}
+
+/// Whether `expr` goes through the account's lamports, via the `lamports`
+/// field or `try_borrow_mut_lamports()`.
+pub fn touches_lamports(expr: &Expr) -> bool {
+    expr.to_token_stream().to_string().contains("lamports")
+}
+
+/// Lines where `func` writes into `field` or a local bound from it, and
+/// the lines of calls whose callee writes into the parameter `field` is
+/// passed as, followed as deep as the callees are attached.
+pub fn field_writes(func: &FnContext, field: &str) -> Vec<usize> {
+    let mut lines = writes_through(func, &func.aliases(field));
+    for callee in &func.callees {
+        if callee
+            .params_for(field)
+            .into_iter()
+            .any(|param| !field_writes(&callee.func, param).is_empty())
+        {
+            lines.push(callee.line);
+        }
+    }
+    lines
+}
+
+struct WriteFinder<'ast> {
+    sites: Vec<WriteSite<'ast>>,
+}
+
+impl<'ast> Visit<'ast> for WriteFinder<'ast> {
+    fn visit_expr_assign(&mut self, node: &'ast syn::ExprAssign) {
+        self.sites.push(WriteSite {
+            line: node.eq_token.span.start().line,
+            target: &node.left,
+            op: None,
+        });
+        syn::visit::visit_expr_assign(self, node);
+    }
+
+    fn visit_expr_binary(&mut self, node: &'ast syn::ExprBinary) {
+        let compound = matches!(
+            node.op,
+            BinOp::AddAssign(_)
+                | BinOp::SubAssign(_)
+                | BinOp::MulAssign(_)
+                | BinOp::DivAssign(_)
+                | BinOp::RemAssign(_)
+                | BinOp::BitOrAssign(_)
+                | BinOp::BitAndAssign(_)
+                | BinOp::BitXorAssign(_)
+                | BinOp::ShlAssign(_)
+                | BinOp::ShrAssign(_)
+        );
+        if compound {
+            self.sites.push(WriteSite {
+                line: node.op.span().start().line,
+                target: &node.left,
+                op: Some(node.op),
+            });
+        }
+        syn::visit::visit_expr_binary(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        if node.method == "set_inner" {
+            self.sites.push(WriteSite {
+                line: node.method.span().start().line,
+                target: &node.receiver,
+                op: None,
+            });
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+}
+
+/// Whether `func`, or a function it calls with the account, compares the
+/// key of the account `field` against something: `require_keys_eq!`,
+/// `==` / `!=` on its key, or `check_id` / `check_program_account`.
+pub fn compares_key(func: &FnContext, field: &str) -> bool {
+    let mut finder = KeyCheckFinder {
+        field,
+        found: false,
+    };
+    func.visit(&mut finder);
+    finder.found
+        || func.callees.iter().any(|c| {
+            c.params_for(field)
+                .into_iter()
+                .any(|param| compares_key(&c.func, param))
+        })
+}
+
+struct KeyCheckFinder<'n> {
+    field: &'n str,
+    found: bool,
+}
+
+impl<'n> KeyCheckFinder<'n> {
+    fn compares_key(&self, node: &impl ToTokens) -> bool {
+        tokens_contain(node, self.field) && tokens_contain(node, "key")
+    }
+}
+
+impl<'n, 'ast> Visit<'ast> for KeyCheckFinder<'n> {
+    fn visit_expr_binary(&mut self, node: &'ast syn::ExprBinary) {
+        if matches!(node.op, syn::BinOp::Eq(_) | syn::BinOp::Ne(_)) && self.compares_key(node) {
+            self.found = true;
+        }
+        syn::visit::visit_expr_binary(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        let comparing = node.path.is_ident("require_keys_eq")
+            || tokens_contain(&node.tokens, "==")
+            || tokens_contain(&node.tokens, "!=");
+        if comparing && self.compares_key(&node.tokens) {
+            self.found = true;
+        }
+        syn::visit::visit_macro(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let Expr::Path(p) = &*node.func {
+            let name = p.path.segments.last().map(|s| s.ident.to_string());
+            if matches!(name.as_deref(), Some("check_id" | "check_program_account"))
+                && tokens_contain(node, self.field)
+            {
+                self.found = true;
+            }
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+}
+
+/// Whether `func` compares something that contains `pattern`, e.g.
+/// `vault_token.mint`, with `==`/`!=`, `.eq()`/`.ne()` or an asserting
+/// macro. Merely reading or logging it does not count.
+pub fn compares(func: &FnContext, pattern: &str) -> bool {
+    let mut finder = ComparisonFinder {
+        pattern,
+        found: false,
+    };
+    func.visit(&mut finder);
+    finder.found
+}
+
+struct ComparisonFinder<'n> {
+    pattern: &'n str,
+    found: bool,
+}
+
+impl<'n, 'ast> Visit<'ast> for ComparisonFinder<'n> {
+    fn visit_expr_binary(&mut self, node: &'ast syn::ExprBinary) {
+        if matches!(node.op, syn::BinOp::Eq(_) | syn::BinOp::Ne(_))
+            && tokens_contain(node, self.pattern)
+        {
+            self.found = true;
+        }
+        syn::visit::visit_expr_binary(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        if (node.method == "eq" || node.method == "ne") && tokens_contain(node, self.pattern) {
+            self.found = true;
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        if compares_in_macro(node) && tokens_contain(&node.tokens, self.pattern) {
+            self.found = true;
+        }
+        syn::visit::visit_macro(self, node);
+    }
+}
+
+/// A value followed through a function: its root name, which may appear as
+/// a field (`ctx.accounts.vault`), and the locals bound from it, which only
+/// count as free identifiers so that `config.data` is not mistaken for a
+/// local named `data`.
+pub struct Aliases {
+    pub root: String,
+    pub locals: HashSet<String>,
+}
+
+impl Aliases {
+    /// Aliases that cover the local `name` alone, with no root to match.
+    pub fn local(name: &str) -> Self {
+        Aliases {
+            root: String::new(),
+            locals: HashSet::from([name.to_string()]),
+        }
+    }
+
+    /// Whether `node` mentions the root or one of its locals.
+    pub fn mentioned_by(&self, node: &impl ToTokens) -> bool {
+        self.matches(&flatten(node.to_token_stream()))
+    }
+
+    fn matches(&self, tokens: &[String]) -> bool {
+        tokens.iter().enumerate().any(|(i, t)| {
+            *t == self.root || (self.locals.contains(t) && (i == 0 || tokens[i - 1] != "."))
+        })
+    }
+}
+
+/// Whether `node` is an assertion that compares: `require_keys_eq!`,
+/// `assert_ne!` and friends, or `require!`/`assert!` over `==`/`!=`.
+/// `msg!` and other macros that merely mention a value do not count.
+pub fn compares_in_macro(node: &Macro) -> bool {
+    let Some(name) = node.path.segments.last().map(|s| s.ident.to_string()) else {
+        return false;
+    };
+    match name.as_str() {
+        "require_keys_eq" | "require_keys_neq" | "require_eq" | "require_neq" | "assert_eq"
+        | "assert_ne" => true,
+        "require" | "assert" => is_comparison(&node.tokens),
+        _ => false,
+    }
+}
+
+/// Whether `node` compares with `==`/`!=` or `.eq()`/`.ne()`.
+pub fn is_comparison(node: &impl ToTokens) -> bool {
+    ["==", "!=", ".eq", ".ne"]
+        .iter()
+        .any(|op| tokens_contain(node, op))
+}
+
+/// Whether the token sequence of `pattern` appears anywhere in `node`.
+pub fn tokens_contain(node: &impl ToTokens, pattern: &str) -> bool {
+    contains_seq(&flatten(node.to_token_stream()), pattern)
+}
+
+fn contains_seq(tokens: &[String], pattern: &str) -> bool {
+    needle(pattern).is_some_and(|needle| contains_needle(tokens, &needle))
+}
+
+/// The tokens of `pattern`, or `None` if it is empty or does not lex.
+fn needle(pattern: &str) -> Option<Vec<String>> {
+    let needle = flatten(pattern.parse::<TokenStream>().ok()?);
+    (!needle.is_empty()).then_some(needle)
+}
+
+fn contains_needle(tokens: &[String], needle: &[String]) -> bool {
+    needle.len() <= tokens.len() && tokens.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Records which identifiers each binding introduces and the tokens of the
+/// expression it was bound from.
+#[derive(Default)]
+struct BindingCollector {
+    bindings: Vec<(Vec<String>, Vec<String>)>,
+}
+
+impl BindingCollector {
+    fn record(&mut self, pat: &syn::Pat, init: &Expr) {
+        let mut idents = PatIdents::default();
+        idents.visit_pat(pat);
+        self.bindings
+            .push((idents.0, flatten(init.to_token_stream())));
+    }
+}
+
+impl<'ast> Visit<'ast> for BindingCollector {
+    fn visit_local(&mut self, node: &'ast syn::Local) {
+        if let Some(init) = &node.init {
+            self.record(&node.pat, &init.expr);
+        }
+        syn::visit::visit_local(self, node);
+    }
+
+    fn visit_expr_let(&mut self, node: &'ast syn::ExprLet) {
+        self.record(&node.pat, &node.expr);
+        syn::visit::visit_expr_let(self, node);
+    }
+
+    fn visit_expr_for_loop(&mut self, node: &'ast syn::ExprForLoop) {
+        self.record(&node.pat, &node.expr);
+        syn::visit::visit_expr_for_loop(self, node);
+    }
+}
+
+struct LocalInit<'n, 'ast> {
+    name: &'n str,
+    init: Option<&'ast Expr>,
+}
+
+impl<'n, 'ast> Visit<'ast> for LocalInit<'n, 'ast> {
+    fn visit_local(&mut self, node: &'ast syn::Local) {
+        if let Some(init) = &node.init {
+            let mut idents = PatIdents::default();
+            idents.visit_pat(&node.pat);
+            if idents.0.iter().any(|i| i == self.name) {
+                self.init = Some(&init.expr);
+            }
+        }
+        syn::visit::visit_local(self, node);
+    }
+}
+
+/// The identifiers a pattern binds, e.g. `a` and `b` for `(a, mut b)`.
+pub fn pat_idents(pat: &syn::Pat) -> Vec<String> {
+    let mut idents = PatIdents::default();
+    idents.visit_pat(pat);
+    idents.0
+}
+
+#[derive(Default)]
+struct PatIdents(Vec<String>);
+
+impl<'ast> Visit<'ast> for PatIdents {
+    fn visit_pat_ident(&mut self, node: &'ast syn::PatIdent) {
+        self.0.push(node.ident.to_string());
+        syn::visit::visit_pat_ident(self, node);
+    }
+}
+
+/// Tracks where a visitor is in the item tree — enclosing modules, impl and
+/// trait blocks, and the stack of functions — so that every function-like
+/// body gets a context regardless of where it is declared. Names start at
+/// the crate root, e.g. `instructions::init::handler`.
+#[derive(Default)]
+pub struct Scope<'ast> {
+    path: Vec<String>,
+    fns: Vec<FnContext<'ast>>,
+    /// Path depth at which the `#[program]` module was entered.
+    program_depth: Option<usize>,
+}
+
+impl<'ast> Scope<'ast> {
+    /// A scope for visiting a file that defines `module`.
+    pub fn in_module(module: &[String]) -> Self {
+        Scope {
+            path: module.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    /// Enter an impl or trait named `segment`.
+    pub fn push(&mut self, segment: String) {
+        self.path.push(segment);
+    }
+
+    pub fn pop(&mut self) {
+        self.path.pop();
+    }
+
+    pub fn enter_mod(&mut self, node: &'ast ItemMod) {
+        if self.program_depth.is_none() && node.attrs.iter().any(|a| a.path().is_ident("program")) {
+            self.program_depth = Some(self.path.len());
+        }
+        self.path.push(node.ident.to_string());
+    }
+
+    pub fn exit_mod(&mut self) {
+        self.path.pop();
+        if self.program_depth == Some(self.path.len()) {
+            self.program_depth = None;
+        }
+    }
+
+    pub fn enter_item_fn(&mut self, node: &'ast ItemFn) {
+        self.enter_fn(Some(&node.sig), FnBody::Block(&node.block));
+    }
+
+    pub fn enter_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        self.enter_fn(Some(&node.sig), FnBody::Block(&node.block));
+    }
+
+    /// Trait methods only get a context when they have a default body.
+    pub fn enter_trait_item_fn(&mut self, node: &'ast TraitItemFn) -> bool {
+        match &node.default {
+            Some(block) => {
+                self.enter_fn(Some(&node.sig), FnBody::Block(block));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Closures are analyzed as part of their enclosing function. Only a
+    /// closure outside any function (e.g. in a `static` initializer) gets its
+    /// own context.
+    pub fn enter_closure(&mut self, body: &'ast Expr) -> bool {
+        if self.fns.is_empty() {
+            self.enter_fn(None, FnBody::Expr(body));
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn exit_fn(&mut self) {
+        self.fns.pop();
+        self.path.pop();
+    }
+
+    pub fn current_fn(&self) -> Option<&FnContext<'ast>> {
+        self.fns.last()
+    }
+
+    /// Qualified name of the innermost function, if any.
+    pub fn qualified_fn(&self) -> Option<String> {
+        self.current_fn().map(|f| f.name.clone())
+    }
+
+    fn enter_fn(&mut self, sig: Option<&'ast Signature>, body: FnBody<'ast>) {
+        let name = sig.map_or_else(|| "{closure}".into(), |s| s.ident.to_string());
+        self.path.push(name);
+        self.fns.push(FnContext::new(
+            self.path.join("::"),
+            sig,
+            body,
+            self.program_depth.is_some(),
+        ));
+    }
+}
+
+/// The name an impl block contributes to a qualified path: the last segment
+/// of its self type, e.g. `Withdraw` for `impl<'info> Withdraw<'info>`.
+pub fn impl_segment(self_ty: &Type) -> String {
+    match self_ty {
+        Type::Path(tp) => tp
+            .path
+            .segments
+            .last()
+            .map(|s| s.ident.to_string())
+            .unwrap_or_else(|| "_".into()),
+        Type::Reference(r) => impl_segment(&r.elem),
+        _ => "_".into(),
+    }
+}
+
+/// Collect a context for every function in `file`, the file defining
+/// `module`, including impl and trait methods and nested functions.
+pub fn collect_fns<'ast>(file: &'ast File, module: &[String]) -> Vec<FnContext<'ast>> {
+    let mut collector = FnCollector {
+        scope: Scope::in_module(module),
+        fns: Vec::new(),
+    };
+    collector.visit_file(file);
+    collector.fns
+}
+
+/// The accounts struct named by a `Context<T>` parameter of `sig`, e.g.
+/// `Withdraw` for `fn withdraw(ctx: Context<Withdraw>, amount: u64)`.
+pub fn context_accounts_type(sig: &Signature) -> Option<String> {
+    context_accounts_path(sig)?
+        .segments
+        .last()
+        .map(|s| s.ident.to_string())
+}
+
+/// The type path of `T` in a `Context<T>` parameter of `sig`, as written,
+/// e.g. `instructions::Withdraw`.
+pub fn context_accounts_path(sig: &Signature) -> Option<&syn::Path> {
+    sig.inputs.iter().find_map(|input| {
+        let syn::FnArg::Typed(pt) = input else {
+            return None;
+        };
+        let Type::Path(tp) = &*pt.ty else {
+            return None;
+        };
+        let seg = tp.path.segments.last()?;
+        if seg.ident != "Context" {
+            return None;
+        }
+        let PathArguments::AngleBracketed(args) = &seg.arguments else {
+            return None;
+        };
+        args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Type(Type::Path(inner)) => Some(&inner.path),
+            _ => None,
+        })
+    })
+}
+
+struct FnCollector<'ast> {
+    scope: Scope<'ast>,
+    fns: Vec<FnContext<'ast>>,
+}
+
+impl<'ast> FnCollector<'ast> {
+    fn record(&mut self) {
+        if let Some(func) = self.scope.current_fn() {
+            self.fns.push(func.clone());
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for FnCollector<'ast> {
+    fn visit_item_mod(&mut self, node: &'ast ItemMod) {
+        self.scope.enter_mod(node);
+        syn::visit::visit_item_mod(self, node);
+        self.scope.exit_mod();
+    }
+
+    fn visit_item_impl(&mut self, node: &'ast syn::ItemImpl) {
+        self.scope.push(impl_segment(&node.self_ty));
+        syn::visit::visit_item_impl(self, node);
+        self.scope.pop();
+    }
+
+    fn visit_item_trait(&mut self, node: &'ast syn::ItemTrait) {
+        self.scope.push(node.ident.to_string());
+        syn::visit::visit_item_trait(self, node);
+        self.scope.pop();
+    }
+
+    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
+        self.scope.enter_item_fn(node);
+        self.record();
+        syn::visit::visit_item_fn(self, node);
+        self.scope.exit_fn();
+    }
+
+    fn visit_impl_item_fn(&mut self, node: &'ast ImplItemFn) {
+        self.scope.enter_impl_item_fn(node);
+        self.record();
+        syn::visit::visit_impl_item_fn(self, node);
+        self.scope.exit_fn();
+    }
+
+    fn visit_trait_item_fn(&mut self, node: &'ast TraitItemFn) {
+        let entered = self.scope.enter_trait_item_fn(node);
+        if entered {
+            self.record();
+        }
+        syn::visit::visit_trait_item_fn(self, node);
+        if entered {
+            self.scope.exit_fn();
+        }
+    }
+}
+
+/// Expands to the `Visit` methods that keep a visitor's `scope` field in
+/// sync with the item tree. Visitors that override one of these methods
+/// themselves cannot use the macro.
+macro_rules! track_scope {
+    () => {
+        fn visit_item_mod(&mut self, node: &'ast syn::ItemMod) {
+            self.scope.enter_mod(node);
+            syn::visit::visit_item_mod(self, node);
+            self.scope.exit_mod();
+        }
+
+        fn visit_item_impl(&mut self, node: &'ast syn::ItemImpl) {
+            self.scope
+                .push($crate::analyzer::context::impl_segment(&node.self_ty));
+            syn::visit::visit_item_impl(self, node);
+            self.scope.pop();
+        }
+
+        fn visit_item_trait(&mut self, node: &'ast syn::ItemTrait) {
+            self.scope.push(node.ident.to_string());
+            syn::visit::visit_item_trait(self, node);
+            self.scope.pop();
+        }
+
+        fn visit_item_fn(&mut self, node: &'ast syn::ItemFn) {
+            self.scope.enter_item_fn(node);
+            syn::visit::visit_item_fn(self, node);
+            self.scope.exit_fn();
+        }
+
+        fn visit_impl_item_fn(&mut self, node: &'ast syn::ImplItemFn) {
+            self.scope.enter_impl_item_fn(node);
+            syn::visit::visit_impl_item_fn(self, node);
+            self.scope.exit_fn();
+        }
+
+        fn visit_trait_item_fn(&mut self, node: &'ast syn::TraitItemFn) {
+            let entered = self.scope.enter_trait_item_fn(node);
+            syn::visit::visit_trait_item_fn(self, node);
+            if entered {
+                self.scope.exit_fn();
+            }
+        }
+
+        fn visit_expr_closure(&mut self, node: &'ast syn::ExprClosure) {
+            let entered = self.scope.enter_closure(&node.body);
+            syn::visit::visit_expr_closure(self, node);
+            if entered {
+                self.scope.exit_fn();
+            }
+        }
+    };
+}
+pub(crate) use track_scope;
+
+/// Render tokens compactly for messages, e.g. `[b"vault", user.key().as_ref()]`
+/// rather than the spaced-out form `TokenStream::to_string` produces.
+pub fn compact_tokens(ts: TokenStream) -> String {
+    fn word(t: &str) -> bool {
+        t.starts_with(|c: char| c.is_alphanumeric() || c == '_' || c == '"' || c == '\'')
+    }
+    fn value_end(t: &str) -> bool {
+        word(t) || matches!(t, ")" | "]" | "}" | "?")
+    }
+
+    let mut out = String::new();
+    let mut prev: Option<String> = None;
+    let mut prev_unary = false;
+    for tok in flatten(ts) {
+        let unary = matches!(tok.as_str(), "&" | "!" | "*" | "-")
+            && !prev.as_deref().is_some_and(value_end);
+        if let Some(p) = prev.as_deref() {
+            let tight = prev_unary
+                || matches!(
+                    tok.as_str(),
+                    "." | ")" | "]" | "," | "::" | "?" | ";" | ".." | "..="
+                )
+                || matches!(p, "." | "(" | "[" | "::" | ".." | "..=")
+                || (matches!(tok.as_str(), "(" | "[") && (value_end(p) || p == "!"))
+                || (tok == "!" && word(p));
+            if !tight {
+                out.push(' ');
+            }
+        }
+        out.push_str(&tok);
+        prev_unary = unary;
+        prev = Some(tok);
+    }
+    out
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+/// Flatten a token stream into strings. Joint punctuation is merged so that
+/// `==` and `::` stay single tokens, and groups keep their delimiters.
+pub fn flatten(ts: TokenStream) -> Vec<String> {
+    let mut out = Vec::new();
+    flatten_into(ts, &mut out);
+    out
+}
+
+fn flatten_into(ts: TokenStream, out: &mut Vec<String>) {
+    let mut punct = String::new();
+    for tt in ts {
+        match tt {
+            TokenTree::Punct(p) => {
+                punct.push(p.as_char());
+                if p.spacing() == Spacing::Alone {
+                    out.push(std::mem::take(&mut punct));
+                }
+            }
+            TokenTree::Ident(i) => {
+                flush(&mut punct, out);
+                out.push(i.to_string());
+            }
+            TokenTree::Literal(l) => {
+                flush(&mut punct, out);
+                out.push(l.to_string());
+            }
+            TokenTree::Group(g) => {
+                flush(&mut punct, out);
+                let (open, close) = match g.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::None => ("", ""),
+                };
+                if !open.is_empty() {
+                    out.push(open.into());
+                }
+                flatten_into(g.stream(), out);
+                if !close.is_empty() {
+                    out.push(close.into());
+                }
+            }
+        }
+    }
+    flush(&mut punct, out);
+}
+
+fn flush(punct: &mut String, out: &mut Vec<String>) {
+    if !punct.is_empty() {
+        out.push(std::mem::take(punct));
+    }
+}
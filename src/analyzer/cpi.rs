@@ -1,145 +1,402 @@
+use super::constraints::{AccountField, AccountsStruct, ConstraintKind};
+use super::context::{
+    compares_key, context_accounts_type, flatten, tokens_contain, track_scope, FnContext, Scope,
+};
+use super::index::CrateIndex;
+use super::pda::{bump_source, find_bump, instruction_params, StoredBumps, TRACE_DEPTH};
+use super::program::ProgramModel;
 use super::{Finding, Severity};
+use quote::ToTokens;
 use syn::spanned::Spanned;
 use syn::visit::Visit;
-use syn::{Expr, ExprCall, ExprMethodCall, File, ItemFn};
+use syn::{Expr, ExprCall, ExprMethodCall, File};
 
 /// Checks for:
-/// 1. invoke_signed calls without bump validation nearby
-/// 2. CPI calls (invoke / invoke_signed) passing unchecked account references
-/// 3. Seeds potentially derived from user-controlled input
-pub fn check_cpi_safety(file: &File, path: &str, source: &str) -> Vec<Finding> {
+/// 1. `invoke_signed` and `CpiContext::new_with_signer` calls whose signer
+///    seeds carry no bump, or a bump taken from instruction data
+/// 2. `invoke` calls made without a signer or key check on the accounts
+///    they pass, here or in every caller
+/// 3. CPIs whose target program comes from an account that is never
+///    checked to be the expected program (arbitrary CPI), including CPIs in
+///    helpers that receive the program from their callers
+///
+/// Seeds derived from user-controlled input are checked in `pda.rs`.
+pub fn check_cpi_safety<'ast>(
+    file: &'ast File,
+    path: &str,
+    model: &ProgramModel<'ast>,
+    index: &CrateIndex<'ast>,
+    bumps: &StoredBumps,
+) -> Vec<Finding> {
     let mut visitor = CpiVisitor {
         path: path.to_string(),
-        source,
         findings: Vec::new(),
-        current_fn: None,
+        scope: Scope::in_module(index.module(path)),
+        model,
+        index,
+        bumps,
     };
     visitor.visit_file(file);
     visitor.findings
 }
 
-struct CpiVisitor<'a> {
+struct CpiVisitor<'a, 'ast> {
     path: String,
-    source: &'a str,
     findings: Vec<Finding>,
-    current_fn: Option<String>,
+    scope: Scope<'ast>,
+    model: &'a ProgramModel<'ast>,
+    index: &'a CrateIndex<'ast>,
+    bumps: &'a StoredBumps,
 }
 
-impl<'a> CpiVisitor<'a> {
+impl<'a, 'ast> CpiVisitor<'a, 'ast> {
     fn line_of_span(&self, span: proc_macro2::Span) -> usize {
         span.start().line
     }
 
-    fn fn_source_text(&self, fn_name: &str) -> Option<&str> {
-        // Simple heuristic: find the function in source
-        self.source.find(&format!("fn {}", fn_name)).map(|start| {
-            let end = self.source.len().min(start + 2000);
-            &self.source[start..end]
-        })
-    }
-
-    fn check_invoke_signed_call(&mut self, span: proc_macro2::Span) {
+    /// Check the signer seeds of an `invoke_signed` (or
+    /// `CpiContext::new_with_signer`) call: they need a bump, and the bump
+    /// must not come from instruction data.
+    fn check_invoke_signed_call(
+        &mut self,
+        call: &str,
+        span: proc_macro2::Span,
+        seeds: Option<&'ast Expr>,
+    ) {
         let line = self.line_of_span(span);
-        let fn_name = self.current_fn.clone().unwrap_or_default();
-
-        // Check if the surrounding function validates bump
-        let has_bump_check = if let Some(fn_src) = self.fn_source_text(&fn_name) {
-            fn_src.contains("bump")
-                && (fn_src.contains("find_program_address")
-                    || fn_src.contains(".bump")
-                    || fn_src.contains("bump =")
-                    || fn_src.contains("bump="))
-        } else {
-            // Fall back to checking nearby lines
-            let lines: Vec<&str> = self.source.lines().collect();
-            let search_start = line.saturating_sub(15);
-            let search_end = (line + 15).min(lines.len());
-            lines[search_start..search_end]
-                .iter()
-                .any(|l| l.contains("bump"))
+        let (Some(func), Some(seeds)) = (self.scope.current_fn(), seeds) else {
+            return;
         };
 
-        if !has_bump_check {
+        let Some(bump) = find_bump(func, seeds, TRACE_DEPTH) else {
             self.findings.push(Finding {
                 severity: Severity::High,
                 check: "invoke-signed-no-bump".into(),
                 message: format!(
-                    "`invoke_signed` call without bump validation. Seeds without a verified \
-                     bump can allow PDA collision attacks. Ensure the bump is derived from \
-                     `find_program_address` or stored/validated on-chain."
+                    "`{}` signer seeds carry no bump. Seeds without a verified bump can allow \
+                     PDA collision attacks. Ensure the bump is derived from \
+                     `find_program_address` or stored/validated on-chain.",
+                    call
+                ),
+                file: self.path.clone(),
+                line,
+                context: self.scope.qualified_fn(),
+            });
+            return;
+        };
+
+        let accounts = self.model.accounts_for(func, &self.path);
+        let source = bump_source(
+            Some(func),
+            accounts,
+            &instruction_params(func),
+            bump,
+            TRACE_DEPTH,
+        );
+        if let Some(reason) = self.bumps.attacker_controlled(&source) {
+            self.findings.push(Finding {
+                severity: Severity::High,
+                check: "non-canonical-bump".into(),
+                message: format!(
+                    "`{}` signs with a bump from {}. A caller can choose a non-canonical bump \
+                     and sign for a different PDA. Use `ctx.bumps` or the bump from \
+                     `find_program_address`.",
+                    call, reason
                 ),
                 file: self.path.clone(),
                 line,
+                context: self.scope.qualified_fn(),
             });
         }
     }
 
-    fn check_invoke_call(&mut self, span: proc_macro2::Span) {
+    /// Check an `invoke` call that hands `passed` (the account infos) to the
+    /// callee program.
+    fn check_invoke_call(&mut self, span: proc_macro2::Span, passed: Option<&'ast Expr>) {
         let line = self.line_of_span(span);
 
-        // Check surrounding context for signer validation
-        let lines: Vec<&str> = self.source.lines().collect();
-        let search_start = line.saturating_sub(20);
-        let search_end = (line + 5).min(lines.len());
-        let context: String = lines[search_start..search_end].join("\n");
-
-        // Look for common patterns that indicate proper validation
-        let has_signer_check = context.contains("is_signer")
-            || context.contains("Signer<")
-            || context.contains(".key()")
-            || context.contains(".key ==")
-            || context.contains("has_one")
-            || context.contains("constraint");
-
-        if !has_signer_check {
+        let validated = self.scope.current_fn().is_some_and(|func| {
+            // With its callees attached, so a check in a helper counts
+            let func = self.index.function(&self.path, &func.name).unwrap_or(func);
+            let passed = passed.and_then(|p| passed_names(func, p, TRACE_DEPTH));
+            self.requires_signer(&self.path, func, passed.as_deref())
+                // A helper making the call relies on what its callers validated
+                || self.validated_by_callers(
+                    &self.path,
+                    func,
+                    passed.as_deref(),
+                    self.index.call_depth,
+                )
+        });
+
+        if !validated {
             self.findings.push(Finding {
                 severity: Severity::Medium,
                 check: "cpi-missing-signer-check".into(),
-                message: format!(
-                    "CPI `invoke` call without apparent signer validation in surrounding \
-                     context. Ensure accounts passed to CPI are properly validated."
-                ),
+                message: "CPI `invoke` call without apparent signer validation in the calling \
+                          function, its accounts struct or its callers. Ensure accounts passed \
+                          to CPI are properly validated."
+                    .into(),
                 file: self.path.clone(),
                 line,
+                context: self.scope.qualified_fn(),
             });
         }
     }
 
+    /// Check the program a CPI targets. `program` is the expression giving
+    /// its id or account: the `CpiContext::new` program argument, or the
+    /// instruction's `program_id`.
+    fn check_cpi_program(&mut self, call: &str, program: &'ast Expr, line: usize) {
+        let Some(func) = self.scope.current_fn() else {
+            return;
+        };
+        // With its callees attached, so a key check in a helper counts
+        let func = self.index.function(&self.path, &func.name).unwrap_or(func);
+        let Some(accounts) = self.model.accounts_for(func, &self.path) else {
+            // A free helper targets whatever program its callers hand it
+            let Some(param) = param_of(func, program, TRACE_DEPTH) else {
+                return;
+            };
+            if compares_key(func, &param) {
+                return;
+            }
+            let mut sources = Vec::new();
+            self.unchecked_program_sources(
+                &self.path,
+                func,
+                &param,
+                self.index.call_depth,
+                &mut sources,
+            );
+            for source in sources {
+                self.findings.push(Finding {
+                    severity: Severity::High,
+                    check: "arbitrary-cpi".into(),
+                    message: format!(
+                        "`{}` targets the program passed in as `{}`, which {}, is never \
+                         checked to be the expected program. A caller can substitute a \
+                         malicious program. Use `Program<'info, T>`, add an `address = ...` \
+                         constraint, or compare the key against the program id before the \
+                         call.",
+                        call, param, source
+                    ),
+                    file: self.path.clone(),
+                    line,
+                    context: self.scope.qualified_fn(),
+                });
+            }
+            return;
+        };
+        let Some(field) = accounts.trace_field(func, program, TRACE_DEPTH) else {
+            return;
+        };
+        if is_pinned_program(field, accounts) {
+            return;
+        }
+        // A helper on the accounts struct relies on whatever its handlers
+        // checked; a handler has to check for itself.
+        let is_handler = func.sig.and_then(context_accounts_type).is_some();
+        let checked = std::iter::once(func)
+            .chain(
+                self.model
+                    .handlers_of(accounts)
+                    .into_iter()
+                    .filter(|_| !is_handler)
+                    .map(|h| &h.func),
+            )
+            .any(|f| compares_key(f, &field.name));
+        if checked {
+            return;
+        }
+
+        let finding = Finding {
+            severity: Severity::High,
+            check: "arbitrary-cpi".into(),
+            message: format!(
+                "`{}` targets the program passed in as `{}` (`{}`) in `{}`, which is never \
+                 checked to be the expected program. A caller can substitute a malicious \
+                 program. Use `Program<'info, T>`, add an `address = ...` constraint, or \
+                 compare `{}.key()` against the program id before the call.",
+                call,
+                field.name,
+                field.type_name(),
+                accounts.name,
+                field.name
+            ),
+            file: self.path.clone(),
+            line,
+            context: self.scope.qualified_fn(),
+        };
+        self.findings.push(finding);
+    }
+
+    /// Whether `func`, declared in `path`, checks `is_signer` itself,
+    /// compares the key of an account it hands the CPI, or takes an accounts
+    /// struct whose accounts handed to the CPI include a signer or a
+    /// constrained account. `passed` holds the names the CPI's account
+    /// infos are built from; `None` when they are not known.
+    fn requires_signer(&self, path: &str, func: &FnContext, passed: Option<&[String]>) -> bool {
+        if func.contains_tokens("is_signer") {
+            return true;
+        }
+        let accounts = self.model.accounts_for(func, path);
+        if accounts.is_some_and(|a| signs_or_constrains(a, passed)) {
+            return true;
+        }
+        let names: Vec<String> = match accounts {
+            Some(accounts) => accounts.fields.iter().map(|f| f.name.clone()).collect(),
+            None => param_names(func),
+        };
+        names
+            .iter()
+            .filter(|name| passed.is_none_or(|p| p.contains(name)))
+            .any(|name| compares_key(func, name))
+    }
+
+    /// Whether every path into `func`, declared in `path`, passes through a
+    /// caller up to `depth` calls away that requires a signer or validates
+    /// the accounts it hands down for `passed`. Functions nobody calls do
+    /// not count as validated.
+    fn validated_by_callers(
+        &self,
+        path: &str,
+        func: &FnContext,
+        passed: Option<&[String]>,
+        depth: usize,
+    ) -> bool {
+        if depth == 0 {
+            return false;
+        }
+        let mut callers = self.index.callers_of(path, &func.name).peekable();
+        callers.peek().is_some()
+            && callers.all(|(site, caller, caller_path)| {
+                // What the caller passes for the parameters the CPI uses
+                let outer = passed.map(|names| {
+                    site.args
+                        .iter()
+                        .filter(|(param, _)| names.contains(param))
+                        .filter_map(|(_, arg)| passed_names(caller, arg, TRACE_DEPTH))
+                        .flatten()
+                        .collect::<Vec<String>>()
+                });
+                caller.contains_tokens("is_signer")
+                    || self
+                        .model
+                        .accounts_for(caller, caller_path)
+                        .is_some_and(|a| signs_or_constrains(a, outer.as_deref()))
+                    || self.validated_by_callers(caller_path, caller, outer.as_deref(), depth - 1)
+            })
+    }
+
+    /// Follow the parameter `param` of the helper `func`, declared in
+    /// `path`, back through up to `depth` callers to the accounts field it
+    /// is filled from, collecting a description of each field that nothing
+    /// pins to one program.
+    fn unchecked_program_sources(
+        &self,
+        path: &str,
+        func: &FnContext<'ast>,
+        param: &str,
+        depth: usize,
+        out: &mut Vec<String>,
+    ) {
+        if depth == 0 {
+            return;
+        }
+        for (site, caller, caller_path) in self.index.callers_of(path, &func.name) {
+            let Some(&(_, arg)) = site.args.iter().find(|(p, _)| p == param) else {
+                continue;
+            };
+            let Some(accounts) = self.model.accounts_for(caller, caller_path) else {
+                if let Some(outer) = param_of(caller, arg, TRACE_DEPTH) {
+                    if !compares_key(caller, &outer) {
+                        self.unchecked_program_sources(caller_path, caller, &outer, depth - 1, out);
+                    }
+                }
+                continue;
+            };
+            let Some(field) = accounts.trace_field(caller, arg, TRACE_DEPTH) else {
+                continue;
+            };
+            let is_handler = caller.sig.and_then(context_accounts_type).is_some();
+            let checked = is_pinned_program(field, accounts)
+                || std::iter::once(caller)
+                    .chain(
+                        self.model
+                            .handlers_of(accounts)
+                            .into_iter()
+                            .filter(|_| !is_handler)
+                            .map(|h| &h.func),
+                    )
+                    .any(|f| compares_key(f, &field.name));
+            if !checked {
+                out.push(format!(
+                    "`{}` fills with `{}` (`{}`) from `{}`",
+                    caller.simple_name(),
+                    field.name,
+                    field.type_name(),
+                    accounts.name
+                ));
+            }
+        }
+    }
+
     fn is_invoke_signed(expr: &Expr) -> bool {
         match expr {
             Expr::Path(p) => p
                 .path
                 .segments
                 .last()
-                .map_or(false, |s| s.ident == "invoke_signed"),
+                .is_some_and(|s| s.ident == "invoke_signed"),
             _ => false,
         }
     }
 
     fn is_invoke(expr: &Expr) -> bool {
         match expr {
-            Expr::Path(p) => p
-                .path
-                .segments
-                .last()
-                .map_or(false, |s| s.ident == "invoke"),
+            Expr::Path(p) => p.path.segments.last().is_some_and(|s| s.ident == "invoke"),
             _ => false,
         }
     }
 }
 
-impl<'a, 'ast> Visit<'ast> for CpiVisitor<'a> {
-    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
-        self.current_fn = Some(node.sig.ident.to_string());
-        syn::visit::visit_item_fn(self, node);
-        self.current_fn = None;
-    }
+impl<'a, 'ast> Visit<'ast> for CpiVisitor<'a, 'ast> {
+    track_scope!();
 
     fn visit_expr_call(&mut self, node: &'ast ExprCall) {
+        let line = node.func.span().start().line;
         if Self::is_invoke_signed(&node.func) {
-            self.check_invoke_signed_call(node.func.span());
+            self.check_invoke_signed_call("invoke_signed", node.func.span(), node.args.last());
         } else if Self::is_invoke(&node.func) {
-            self.check_invoke_call(node.func.span());
+            self.check_invoke_call(node.func.span(), node.args.iter().nth(1));
+        }
+
+        if Self::is_invoke_signed(&node.func) || Self::is_invoke(&node.func) {
+            let func = self.scope.current_fn();
+            if let Some(program) = node
+                .args
+                .first()
+                .and_then(|ix| func.and_then(|f| instruction_program_id(f, ix, TRACE_DEPTH)))
+            {
+                let call = if Self::is_invoke_signed(&node.func) {
+                    "invoke_signed"
+                } else {
+                    "invoke"
+                };
+                self.check_cpi_program(call, program, line);
+            }
+        } else if is_cpi_context_new(&node.func) {
+            if let Some(program) = node.args.first() {
+                self.check_cpi_program("CpiContext", program, line);
+            }
+            if is_cpi_context_with_signer(&node.func) {
+                self.check_invoke_signed_call(
+                    "CpiContext::new_with_signer",
+                    node.func.span(),
+                    node.args.last(),
+                );
+            }
         }
         syn::visit::visit_expr_call(self, node);
     }
@@ -147,10 +404,290 @@ impl<'a, 'ast> Visit<'ast> for CpiVisitor<'a> {
     fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
         let method = node.method.to_string();
         if method == "invoke_signed" {
-            self.check_invoke_signed_call(node.method.span());
+            self.check_invoke_signed_call("invoke_signed", node.method.span(), node.args.last());
         } else if method == "invoke" {
-            self.check_invoke_call(node.method.span());
+            self.check_invoke_call(node.method.span(), None);
         }
         syn::visit::visit_expr_method_call(self, node);
     }
 }
+
+/// Whether one of the `passed` fields of `accounts` (any field when
+/// `passed` is `None`) is a signer or is constrained with `signer`,
+/// `has_one` or a `constraint`.
+fn signs_or_constrains(accounts: &AccountsStruct, passed: Option<&[String]>) -> bool {
+    accounts.fields.iter().any(|f| {
+        passed.is_none_or(|p| p.contains(&f.name))
+            && (f.type_name() == "Signer"
+                || f.has(ConstraintKind::Signer)
+                || f.has(ConstraintKind::HasOne)
+                || f.has(ConstraintKind::Constraint))
+    })
+}
+
+/// The identifiers the account infos `expr` handed to a CPI are built from,
+/// following up to `depth` locals: `[vault, to_account_info, ...]` for
+/// `&[ctx.accounts.vault.to_account_info()]`. `None` when the whole
+/// accounts struct is passed (`to_account_infos()`), so any field counts.
+fn passed_names(func: &FnContext, expr: &Expr, depth: usize) -> Option<Vec<String>> {
+    let mut names = flatten(expr.to_token_stream());
+    if names.iter().any(|t| t == "to_account_infos") {
+        return None;
+    }
+    if depth > 0 {
+        let locals: Vec<&Expr> = names
+            .iter()
+            .filter_map(|name| func.local_init(name))
+            .collect();
+        for init in locals {
+            names.extend(passed_names(func, init, depth - 1)?);
+        }
+    }
+    Some(names)
+}
+
+/// The names of the plain `name: Type` parameters of `func`.
+fn param_names(func: &FnContext) -> Vec<String> {
+    let Some(sig) = func.sig else {
+        return Vec::new();
+    };
+    sig.inputs
+        .iter()
+        .filter_map(|input| match input {
+            syn::FnArg::Typed(pt) => match &*pt.pat {
+                syn::Pat::Ident(pi) => Some(pi.ident.to_string()),
+                _ => None,
+            },
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// The parameter of `func` that `expr` is computed from, directly
+/// (`*program.key`, `token_program.to_account_info()`) or through up to
+/// `depth` locals.
+fn param_of(func: &FnContext, expr: &Expr, depth: usize) -> Option<String> {
+    if let Some(param) = param_names(func)
+        .into_iter()
+        .find(|p| tokens_contain(expr, p))
+    {
+        return Some(param);
+    }
+    match strip(expr) {
+        Expr::Path(p) if depth > 0 => {
+            let init = func.local_init(&p.path.get_ident()?.to_string())?;
+            param_of(func, init, depth - 1)
+        }
+        _ => None,
+    }
+}
+
+/// `CpiContext::new` and `CpiContext::new_with_signer`.
+fn is_cpi_context_new(func: &Expr) -> bool {
+    let Expr::Path(p) = func else {
+        return false;
+    };
+    let segments: Vec<String> = p
+        .path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect();
+    matches!(
+        segments.as_slice(),
+        [.., ty, ctor] if ty == "CpiContext" && ctor.starts_with("new")
+    )
+}
+
+fn is_cpi_context_with_signer(func: &Expr) -> bool {
+    matches!(func, Expr::Path(p) if p.path.segments.last().is_some_and(|s| s.ident == "new_with_signer"))
+}
+
+/// Look through `&`, parentheses, `?`, `.unwrap()` and friends.
+fn strip(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Reference(r) => strip(&r.expr),
+        Expr::Paren(p) => strip(&p.expr),
+        Expr::Group(g) => strip(&g.expr),
+        Expr::Try(t) => strip(&t.expr),
+        Expr::MethodCall(m)
+            if matches!(
+                m.method.to_string().as_str(),
+                "unwrap" | "expect" | "clone" | "to_owned"
+            ) =>
+        {
+            strip(&m.receiver)
+        }
+        _ => expr,
+    }
+}
+
+/// The `program_id` of the instruction `ix`: the field of an
+/// `Instruction { .. }` literal, or the first argument of an
+/// `Instruction::new_with_*` call or an SPL-style instruction builder. Locals are
+/// followed to their `let` binding.
+fn instruction_program_id<'ast>(
+    func: &FnContext<'ast>,
+    ix: &'ast Expr,
+    depth: usize,
+) -> Option<&'ast Expr> {
+    match strip(ix) {
+        Expr::Path(p) if depth > 0 => {
+            let init = func.local_init(&p.path.get_ident()?.to_string())?;
+            instruction_program_id(func, init, depth - 1)
+        }
+        Expr::Struct(s) if s.path.segments.last()?.ident == "Instruction" => s
+            .fields
+            .iter()
+            .find(|f| matches!(&f.member, syn::Member::Named(n) if n == "program_id"))
+            .map(|f| &f.expr),
+        // `Instruction::new_with_bytes(program_id, ..)` and SPL builders
+        // such as `spl_token::instruction::transfer(token_program_id, ..)`.
+        // `system_instruction::*` builders hard-code the system program.
+        Expr::Call(c) => match &*c.func {
+            Expr::Path(p)
+                if p.path
+                    .segments
+                    .iter()
+                    .any(|s| s.ident == "Instruction" || s.ident == "instruction") =>
+            {
+                c.args.first()
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether the accounts struct itself pins `field` to one program: a
+/// `Program`/`Interface` type, an `address = ...` constraint, or a
+/// `constraint = ...` comparing its key.
+fn is_pinned_program(field: &AccountField, accounts: &AccountsStruct) -> bool {
+    matches!(field.type_name().as_str(), "Program" | "Interface")
+        || field.has(ConstraintKind::Address)
+        || accounts.fields.iter().any(|f| {
+            f.constraints.iter().any(|c| {
+                c.kind == ConstraintKind::Constraint
+                    && c.value.as_ref().is_some_and(|v| {
+                        tokens_contain(v, &field.name)
+                            && tokens_contain(v, "key")
+                            && (tokens_contain(v, "==") || tokens_contain(v, "!="))
+                    })
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::check_source;
+
+    #[test]
+    fn unchecked_cpi_target() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod payer {
+                pub fn pay(ctx: Context<Pay>, amount: u64) -> Result<()> {
+                    let cpi = CpiContext::new(ctx.accounts.token_program.to_account_info(), accounts);
+                    token::transfer(cpi, amount)
+                }
+
+                pub fn relay(ctx: Context<Pay>) -> Result<()> {
+                    forward(&ctx.accounts.token_program)
+                }
+            }
+
+            fn forward(program: &AccountInfo) -> Result<()> {
+                let cpi = CpiContext::new(program.clone(), accounts);
+                Ok(())
+            }
+
+            #[derive(Accounts)]
+            pub struct Pay<'info> {
+                pub token_program: AccountInfo<'info>,
+            }
+            "#,
+            "arbitrary-cpi",
+        );
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [5, 15], "{findings:?}");
+        assert!(findings[1]
+            .message
+            .contains("`relay` fills with `token_program`"));
+    }
+
+    #[test]
+    fn pinned_cpi_target() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod payer {
+                pub fn pay(ctx: Context<Pay>, amount: u64) -> Result<()> {
+                    let cpi = CpiContext::new(ctx.accounts.token_program.to_account_info(), accounts);
+                    token::transfer(cpi, amount)?;
+                    require_keys_eq!(ctx.accounts.other_program.key(), token::ID);
+                    let cpi = CpiContext::new(ctx.accounts.other_program.to_account_info(), accounts);
+                    token::transfer(cpi, amount)
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Pay<'info> {
+                pub token_program: Program<'info, Token>,
+                pub other_program: AccountInfo<'info>,
+            }
+            "#,
+            "arbitrary-cpi",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+
+    #[test]
+    fn cpi_without_passed_signer() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn drain(ctx: Context<Drain>) -> Result<()> {
+                    let ix = system_instruction::transfer(&ctx.accounts.vault.key(), &ctx.accounts.to.key(), 10);
+                    invoke(&ix, &[ctx.accounts.vault.to_account_info(), ctx.accounts.to.to_account_info()])
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Drain<'info> {
+                pub payer: Signer<'info>,
+                pub vault: AccountInfo<'info>,
+                pub to: AccountInfo<'info>,
+            }
+            "#,
+            "cpi-missing-signer-check",
+        );
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [6], "{findings:?}");
+    }
+
+    #[test]
+    fn cpi_with_passed_signer() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn pay(ctx: Context<Pay>) -> Result<()> {
+                    let ix = system_instruction::transfer(&ctx.accounts.payer.key(), &ctx.accounts.to.key(), 10);
+                    let infos = [ctx.accounts.payer.to_account_info(), ctx.accounts.to.to_account_info()];
+                    invoke(&ix, &infos)
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Pay<'info> {
+                pub payer: Signer<'info>,
+                pub to: AccountInfo<'info>,
+            }
+            "#,
+            "cpi-missing-signer-check",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+}
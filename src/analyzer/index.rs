@@ -0,0 +1,946 @@
+use super::context::{collect_fns, flatten, Callee, FnContext};
+use quote::ToTokens;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{Expr, File, Item, ItemConst, ItemStruct};
+
+/// How many `use` re-exports a lookup follows before giving up, so that
+/// glob cycles (`pub use a::*` / `pub use b::*`) terminate.
+const REEXPORT_DEPTH: usize = 8;
+
+/// A scanned file and where it sits in its crate.
+pub struct IndexedFile<'ast> {
+    pub path: String,
+    pub syntax: &'ast File,
+    krate: usize,
+    /// The module the file defines, e.g. `["instructions", "withdraw"]`.
+    pub module: Vec<String>,
+}
+
+/// A named item together with where it is declared.
+pub struct Symbol<T> {
+    pub name: String,
+    pub file: String,
+    krate: usize,
+    /// The module declaring the item, including inline `mod` blocks. For
+    /// methods and functions nested in other functions it continues with
+    /// the impl type or enclosing function, e.g. `["vault", "Withdraw"]`.
+    pub module: Vec<String>,
+    pub item: T,
+}
+
+/// A `use` item: `alias` stands for `path` inside `module`. Paths keep
+/// their `crate`/`self`/`super` prefix; globs have no alias.
+struct Import {
+    krate: usize,
+    module: Vec<String>,
+    alias: Option<String>,
+    path: Vec<String>,
+}
+
+/// A call from one indexed function to another.
+pub struct CallSite<'ast> {
+    pub caller: usize,
+    pub callee: usize,
+    pub line: usize,
+    /// Each parameter of the callee (`self` for the receiver) with the
+    /// argument passed for it.
+    pub args: Vec<(String, &'ast Expr)>,
+}
+
+/// Every scanned file placed in its crate, with the structs, functions and
+/// constants each crate declares. Modules are resolved from `mod`
+/// declarations starting at each `lib.rs`/`main.rs`, following `#[path]`,
+/// so checks can look up a definition wherever it lives.
+pub struct CrateIndex<'ast> {
+    files: Vec<IndexedFile<'ast>>,
+    pub structs: Vec<Symbol<&'ast ItemStruct>>,
+    pub consts: Vec<Symbol<&'ast ItemConst>>,
+    /// Every function and method. Functions are placed in the module of
+    /// their file; `FnContext::name` is qualified from the crate root,
+    /// through inline modules and impl blocks.
+    pub fns: Vec<Symbol<FnContext<'ast>>>,
+    imports: Vec<Import>,
+    /// Every module of each crate, from files and inline `mod` blocks.
+    modules: Vec<(usize, Vec<String>)>,
+    /// Parallel to `fns`: whether the function is in the `#[program]`
+    /// module or called from it, directly or through other functions.
+    reached: Vec<bool>,
+    /// Calls between functions of the same crate.
+    pub calls: Vec<CallSite<'ast>>,
+    /// How many calls deep checks follow a function into its callees.
+    pub call_depth: usize,
+}
+
+impl<'ast> CrateIndex<'ast> {
+    /// Index `(path, syntax)` pairs, following calls `call_depth` deep.
+    pub fn build(files: &[(String, &'ast File)], call_depth: usize) -> Self {
+        let mut index = CrateIndex {
+            files: place_files(files),
+            structs: Vec::new(),
+            consts: Vec::new(),
+            fns: Vec::new(),
+            imports: Vec::new(),
+            modules: Vec::new(),
+            reached: Vec::new(),
+            calls: Vec::new(),
+            call_depth,
+        };
+
+        for i in 0..index.files.len() {
+            let (path, syntax, krate) = {
+                let file = &index.files[i];
+                (file.path.clone(), file.syntax, file.krate)
+            };
+            let mut module = index.files[i].module.clone();
+            index.modules.push((krate, module.clone()));
+            index.collect_items(&syntax.items, &path, krate, &mut module);
+            let module = index.files[i].module.clone();
+            index
+                .fns
+                .extend(collect_fns(syntax, &module).into_iter().map(|func| {
+                    let mut scope: Vec<String> = func.name.split("::").map(String::from).collect();
+                    let name = scope.pop().unwrap_or_default();
+                    Symbol {
+                        name,
+                        file: path.clone(),
+                        krate,
+                        module: scope,
+                        item: func,
+                    }
+                }));
+        }
+
+        index.calls = (0..index.fns.len())
+            .flat_map(|i| index.call_sites(i))
+            .collect();
+        index.reached = index.reach_from_program();
+        let mut memo = HashMap::new();
+        let expanded: Vec<FnContext<'ast>> = (0..index.fns.len())
+            .map(|i| index.expand(i, call_depth, &mut vec![i], &mut memo))
+            .map(|func| Rc::try_unwrap(func).unwrap_or_else(|shared| (*shared).clone()))
+            .collect();
+        for (symbol, func) in index.fns.iter_mut().zip(expanded) {
+            symbol.item = func;
+        }
+        index
+    }
+
+    /// The calls function `i` makes to other functions of its crate.
+    /// Qualified paths only count when they start in a local module, so
+    /// `token::transfer` is not taken for a local `transfer`; `Self::`
+    /// paths resolve in the caller's impl block and bare names first among
+    /// functions nested in the caller; method calls need a single local
+    /// method of that name.
+    fn call_sites(&self, i: usize) -> Vec<CallSite<'ast>> {
+        let caller = &self.fns[i];
+        let mut finder = CallFinder::default();
+        caller.item.visit(&mut finder);
+
+        let mut sites = Vec::new();
+        for (line, call) in finder.calls {
+            let (callee, args): (Option<usize>, Vec<&'ast Expr>) = match call {
+                Call::Path(path, args) => {
+                    let segments: Vec<String> =
+                        path.segments.iter().map(|s| s.ident.to_string()).collect();
+                    let scoped = match segments.as_slice() {
+                        [first, rest @ ..] if first == "Self" => {
+                            Some([caller.module.as_slice(), rest].concat())
+                        }
+                        [name] => {
+                            let mut nested: Vec<String> =
+                                caller.item.name.split("::").map(String::from).collect();
+                            nested.push(name.clone());
+                            Some(nested)
+                        }
+                        _ => None,
+                    };
+                    if let Some(callee) = scoped
+                        .and_then(|abs| self.lookup(&self.fns, caller.krate, &abs, REEXPORT_DEPTH))
+                    {
+                        sites.extend(self.call_site(i, callee, line, args));
+                        continue;
+                    }
+                    let local = match segments.first() {
+                        Some(first) if segments.len() > 1 => {
+                            matches!(first.as_str(), "crate" | "self" | "super" | "Self")
+                                || self.is_local_module(caller.krate, first)
+                                || self.is_local_impl(caller.krate, first)
+                        }
+                        _ => true,
+                    };
+                    let callee = local
+                        .then(|| {
+                            let module = self.enclosing_module(caller.krate, &caller.module);
+                            self.resolve(&self.fns, &caller.file, module, path)
+                        })
+                        .flatten()
+                        .filter(|&j| self.fns[j].krate == caller.krate);
+                    (callee, args)
+                }
+                Call::Method(method, args) => {
+                    let mut candidates = (0..self.fns.len()).filter(|&j| {
+                        let f = &self.fns[j];
+                        f.krate == caller.krate
+                            && f.name == method
+                            && f.item.sig.is_some_and(|s| s.receiver().is_some())
+                    });
+                    let first = candidates.next();
+                    (first.filter(|_| candidates.next().is_none()), args)
+                }
+            };
+            if let Some(callee) = callee {
+                sites.extend(self.call_site(i, callee, line, args));
+            }
+        }
+        sites
+    }
+
+    /// The call from function `caller` to `callee` at `line`, with each
+    /// parameter bound to its argument. Recursive calls are left out.
+    fn call_site(
+        &self,
+        caller: usize,
+        callee: usize,
+        line: usize,
+        args: Vec<&'ast Expr>,
+    ) -> Option<CallSite<'ast>> {
+        if callee == caller {
+            return None;
+        }
+        let sig = self.fns[callee].item.sig?;
+        let params = sig.inputs.iter().map(|input| match input {
+            syn::FnArg::Receiver(_) => Some("self".to_string()),
+            syn::FnArg::Typed(pt) => match &*pt.pat {
+                syn::Pat::Ident(pi) => Some(pi.ident.to_string()),
+                _ => None,
+            },
+        });
+        Some(CallSite {
+            caller,
+            callee,
+            line,
+            args: params
+                .zip(args)
+                .filter_map(|(param, arg)| Some((param?, arg)))
+                .collect(),
+        })
+    }
+
+    /// Whether `name` is a type of `krate` with methods, as the first
+    /// segment of a path such as `Vault::check`.
+    fn is_local_impl(&self, krate: usize, name: &str) -> bool {
+        self.fns.iter().any(|f| {
+            f.krate == krate
+                && f.module.last().is_some_and(|m| m == name)
+                && !self.modules.contains(&(krate, f.module.clone()))
+        })
+    }
+
+    /// The innermost module of `krate` that `scope` is in, dropping impl
+    /// types and enclosing functions, e.g. `["b"]` for `["b", "Vault"]`.
+    fn enclosing_module<'s>(&self, krate: usize, scope: &'s [String]) -> &'s [String] {
+        let mut module = scope;
+        while !module.is_empty() && !self.modules.contains(&(krate, module.to_vec())) {
+            module = &module[..module.len() - 1];
+        }
+        module
+    }
+
+    /// Whether `name` is a module of `krate`, as the first segment of a
+    /// path.
+    fn is_local_module(&self, krate: usize, name: &str) -> bool {
+        self.modules
+            .iter()
+            .any(|(k, module)| *k == krate && module.iter().any(|segment| segment == name))
+    }
+
+    /// Function `i` with its callees attached, `depth` calls deep. `stack`
+    /// holds the functions being expanded, so recursion stops. Expansions
+    /// are shared through `memo`, keyed by function and remaining depth, so
+    /// a helper reached along many paths is expanded once per depth rather
+    /// than once per path.
+    fn expand(
+        &self,
+        i: usize,
+        depth: usize,
+        stack: &mut Vec<usize>,
+        memo: &mut HashMap<(usize, usize), Rc<FnContext<'ast>>>,
+    ) -> Rc<FnContext<'ast>> {
+        if let Some(func) = memo.get(&(i, depth)) {
+            return func.clone();
+        }
+        let func = self.fns[i].item.clone();
+        if depth == 0 {
+            return Rc::new(func);
+        }
+        let mut callees = Vec::new();
+        for site in self.calls.iter().filter(|c| c.caller == i) {
+            if stack.contains(&site.callee) {
+                continue;
+            }
+            stack.push(site.callee);
+            callees.push(Callee {
+                func: self.expand(site.callee, depth - 1, stack, memo),
+                bindings: site
+                    .args
+                    .iter()
+                    .map(|(param, arg)| (param.clone(), flatten(arg.to_token_stream())))
+                    .collect(),
+                line: site.line,
+            });
+            stack.pop();
+        }
+        let func = Rc::new(func.with_callees(callees));
+        memo.insert((i, depth), func.clone());
+        func
+    }
+
+    /// The indexed functions declared in `path`, with their callees.
+    pub fn fns_in<'i>(&'i self, path: &'i str) -> impl Iterator<Item = &'i FnContext<'ast>> + 'i {
+        self.fns
+            .iter()
+            .filter(move |f| f.file == path)
+            .map(|f| &f.item)
+    }
+
+    /// The indexed function declared in `path` as `name`, with its callees.
+    pub fn function(&self, path: &str, name: &str) -> Option<&FnContext<'ast>> {
+        self.fns
+            .iter()
+            .find(|f| f.file == path && f.item.name == name)
+            .map(|f| &f.item)
+    }
+
+    /// The calls into the function declared in `path` as `name`, with the
+    /// calling function and the file it is declared in.
+    pub fn callers_of<'i>(
+        &'i self,
+        path: &'i str,
+        name: &'i str,
+    ) -> impl Iterator<Item = (&'i CallSite<'ast>, &'i FnContext<'ast>, &'i str)> + 'i {
+        self.calls
+            .iter()
+            .filter(move |c| {
+                let callee = &self.fns[c.callee];
+                callee.file == path && callee.item.name == name
+            })
+            .map(|c| {
+                let caller = &self.fns[c.caller];
+                (c, &caller.item, caller.file.as_str())
+            })
+    }
+
+    fn collect_items(
+        &mut self,
+        items: &'ast [Item],
+        path: &str,
+        krate: usize,
+        module: &mut Vec<String>,
+    ) {
+        for item in items {
+            match item {
+                Item::Struct(s) => self.structs.push(Symbol {
+                    name: s.ident.to_string(),
+                    file: path.to_string(),
+                    krate,
+                    module: module.clone(),
+                    item: s,
+                }),
+                Item::Const(c) => self.consts.push(Symbol {
+                    name: c.ident.to_string(),
+                    file: path.to_string(),
+                    krate,
+                    module: module.clone(),
+                    item: c,
+                }),
+                Item::Use(u) => {
+                    let mut uses = UseCollector::default();
+                    uses.collect(&u.tree);
+                    self.imports
+                        .extend(uses.imports.into_iter().map(|(alias, path)| Import {
+                            krate,
+                            module: module.clone(),
+                            alias,
+                            path,
+                        }));
+                }
+                Item::Mod(m) => {
+                    if let Some((_, items)) = &m.content {
+                        module.push(m.ident.to_string());
+                        self.modules.push((krate, module.clone()));
+                        self.collect_items(items, path, krate, module);
+                        module.pop();
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Marks the `#[program]` functions and everything they reach through
+    /// the resolved call edges in `calls`.
+    fn reach_from_program(&self) -> Vec<bool> {
+        let mut reached: Vec<bool> = self.fns.iter().map(|f| f.item.in_program).collect();
+        let mut worklist: Vec<usize> = (0..self.fns.len()).filter(|&i| reached[i]).collect();
+
+        while let Some(i) = worklist.pop() {
+            for site in self.calls.iter().filter(|c| c.caller == i) {
+                if !reached[site.callee] {
+                    reached[site.callee] = true;
+                    worklist.push(site.callee);
+                }
+            }
+        }
+
+        reached
+    }
+
+    fn file(&self, path: &str) -> Option<&IndexedFile<'ast>> {
+        self.files.iter().find(|f| f.path == path)
+    }
+
+    /// The module the file at `path` defines, empty for a crate root.
+    pub fn module(&self, path: &str) -> &[String] {
+        self.file(path).map_or(&[], |f| f.module.as_slice())
+    }
+
+    /// The functions of the `#[program]` module, in any file of the crate,
+    /// together with every function they reach, restricted to those
+    /// declared in `path`.
+    pub fn program_fns<'i>(
+        &'i self,
+        path: &'i str,
+    ) -> impl Iterator<Item = &'i FnContext<'ast>> + 'i {
+        self.fns
+            .iter()
+            .zip(&self.reached)
+            .filter(move |(f, reached)| **reached && f.file == path)
+            .map(|(f, _)| &f.item)
+    }
+
+    /// The structs of the crate `path` belongs to, the ones declared in
+    /// `path` first.
+    pub fn crate_structs<'i>(
+        &'i self,
+        path: &'i str,
+    ) -> impl Iterator<Item = &'ast ItemStruct> + 'i {
+        let krate = self.file(path).map(|f| f.krate);
+        let own = self.structs.iter().filter(move |s| s.file == path);
+        let rest = self
+            .structs
+            .iter()
+            .filter(move |s| s.file != path && Some(s.krate) == krate);
+        own.chain(rest).map(|s| s.item)
+    }
+
+    /// The struct a type path written in `path` refers to, as an index into
+    /// `structs`.
+    pub fn resolve_struct(&self, path: &str, ty: &syn::Path) -> Option<usize> {
+        let module = self.module(path);
+        self.resolve(&self.structs, path, module, ty)
+    }
+
+    /// Resolve `ty`, written in `module` of file `path`, among `symbols`:
+    /// through the module's `use` items and re-exports, then relative to
+    /// the module, then from the crate root. Paths that cannot be followed
+    /// (e.g. through a module that was not scanned) fall back to the name,
+    /// preferring the module the path names, then the same file, then the
+    /// same crate. The fallback is skipped for paths imported from another
+    /// crate, and a bare name never falls back to a method or a function
+    /// nested in another one.
+    fn resolve<T>(
+        &self,
+        symbols: &[Symbol<T>],
+        path: &str,
+        module: &[String],
+        ty: &syn::Path,
+    ) -> Option<usize> {
+        let segments: Vec<String> = ty.segments.iter().map(|s| s.ident.to_string()).collect();
+        let file = self.file(path)?;
+
+        let mut tries = Vec::new();
+        if let [first, rest @ ..] = segments.as_slice() {
+            if !rest.is_empty() {
+                // `ix::Withdraw` with `use crate::instructions as ix;`
+                for import in self.imports_in(file.krate, module, first) {
+                    for base in absolutize(&import.module, &import.path) {
+                        tries.push([base.as_slice(), rest].concat());
+                    }
+                }
+            }
+        }
+        tries.extend(absolutize(module, &segments));
+        if let Some(found) = tries
+            .iter()
+            .find_map(|t| self.lookup(symbols, file.krate, t, REEXPORT_DEPTH))
+        {
+            return Some(found);
+        }
+
+        if self.resolves_outside(file.krate, module, &segments) {
+            return None;
+        }
+        let (name, qualifier) = segments.split_last()?;
+        let qualifier: Vec<String> = qualifier
+            .iter()
+            .filter(|s| !matches!(s.as_str(), "crate" | "self" | "super"))
+            .cloned()
+            .collect();
+        let candidates: Vec<usize> = (0..symbols.len())
+            .filter(|&i| {
+                let s = &symbols[i];
+                s.name == *name
+                    && (self.modules.contains(&(s.krate, s.module.clone()))
+                        || (!qualifier.is_empty() && s.module.ends_with(&qualifier)))
+            })
+            .collect();
+        if candidates.len() <= 1 {
+            return candidates.first().copied();
+        }
+        let prefer = |pred: &dyn Fn(&Symbol<T>) -> bool| {
+            candidates.iter().copied().find(|&i| pred(&symbols[i]))
+        };
+        prefer(&|s| !qualifier.is_empty() && s.module.ends_with(&qualifier))
+            .or_else(|| prefer(&|s| s.file == path))
+            .or_else(|| prefer(&|s| s.krate == file.krate))
+            .or(candidates.first().copied())
+    }
+
+    /// The symbol at the absolute path `abs` of `krate`, following `use`
+    /// items in the module it names. As in Rust, a name imported by an
+    /// explicit `use` wins over a glob import, and an item declared in the
+    /// module wins over a glob.
+    fn lookup<T>(
+        &self,
+        symbols: &[Symbol<T>],
+        krate: usize,
+        abs: &[String],
+        depth: usize,
+    ) -> Option<usize> {
+        let (name, module) = abs.split_last()?;
+        let follow = |glob: bool| {
+            self.imports
+                .iter()
+                .filter(|i| i.krate == krate && i.module == module)
+                .filter(move |i| match &i.alias {
+                    Some(alias) => !glob && alias == name,
+                    None => glob,
+                })
+                .flat_map(|i| {
+                    absolutize(&i.module, &i.path)
+                        .into_iter()
+                        .map(move |mut target| {
+                            if i.alias.is_none() {
+                                target.push(name.clone());
+                            }
+                            target
+                        })
+                })
+        };
+        if depth > 0 {
+            let mut explicit = follow(false).peekable();
+            if explicit.peek().is_some() {
+                return explicit.find_map(|target| self.lookup(symbols, krate, &target, depth - 1));
+            }
+        }
+        if let Some(i) = symbols
+            .iter()
+            .position(|s| s.krate == krate && s.module == module && s.name == *name)
+        {
+            return Some(i);
+        }
+        if depth == 0 {
+            return None;
+        }
+        follow(true).find_map(|target| self.lookup(symbols, krate, &target, depth - 1))
+    }
+
+    /// Whether `segments`, written in `module`, names something outside the
+    /// crate: its first segment (the whole name for a single segment) is
+    /// bound by a `use` of another crate, e.g. `transfer` after
+    /// `use anchor_spl::token::transfer;`.
+    fn resolves_outside(&self, krate: usize, module: &[String], segments: &[String]) -> bool {
+        let Some(first) = segments.first() else {
+            return false;
+        };
+        self.imports_in(krate, module, first).any(|import| {
+            import.path.first().is_some_and(|root| {
+                !matches!(root.as_str(), "crate" | "self" | "super")
+                    && !self.is_local_module(krate, root)
+            })
+        })
+    }
+
+    fn imports_in<'i>(
+        &'i self,
+        krate: usize,
+        module: &'i [String],
+        alias: &'i str,
+    ) -> impl Iterator<Item = &'i Import> + 'i {
+        self.imports.iter().filter(move |i| {
+            i.krate == krate && i.module == module && i.alias.as_deref() == Some(alias)
+        })
+    }
+}
+
+/// The absolute module paths `path`, written inside `module`, may stand
+/// for: `crate::` and `super::` are anchored, anything else is tried
+/// relative to `module` and then from the crate root.
+fn absolutize(module: &[String], path: &[String]) -> Vec<Vec<String>> {
+    match path.first().map(String::as_str) {
+        Some("crate") => vec![path[1..].to_vec()],
+        Some("self") => vec![[module, &path[1..]].concat()],
+        Some("super") => {
+            let mut base = module.to_vec();
+            let mut rest = path;
+            while rest.first().is_some_and(|s| s == "super") {
+                base.pop();
+                rest = &rest[1..];
+            }
+            vec![[base.as_slice(), rest].concat()]
+        }
+        _ if module.is_empty() => vec![path.to_vec()],
+        _ => vec![[module, path].concat(), path.to_vec()],
+    }
+}
+
+/// Assign every file a crate and a module. Crates are rooted at `lib.rs`
+/// and `main.rs`; files no `mod` declaration reaches get the module their
+/// path below `src/` suggests.
+fn place_files<'ast>(files: &[(String, &'ast File)]) -> Vec<IndexedFile<'ast>> {
+    let positions: HashMap<PathBuf, usize> = files
+        .iter()
+        .enumerate()
+        .map(|(i, (path, _))| (PathBuf::from(path), i))
+        .collect();
+    let mut placed: Vec<Option<(usize, Vec<String>)>> = vec![None; files.len()];
+
+    let roots: Vec<usize> = (0..files.len())
+        .filter(|&i| {
+            matches!(
+                Path::new(&files[i].0).file_name().and_then(|n| n.to_str()),
+                Some("lib.rs" | "main.rs")
+            )
+        })
+        .collect();
+    for (krate, &root) in roots.iter().enumerate() {
+        if placed[root].is_some() {
+            continue;
+        }
+        placed[root] = Some((krate, Vec::new()));
+        let mut worklist = vec![root];
+        while let Some(i) = worklist.pop() {
+            let path = Path::new(&files[i].0);
+            let module = placed[i]
+                .as_ref()
+                .map(|(_, m)| m.clone())
+                .unwrap_or_default();
+            let mut children = Vec::new();
+            declared_mods(
+                &files[i].1.items,
+                &child_dir(path),
+                path.parent(),
+                &mut Vec::new(),
+                &mut children,
+            );
+            for (inner, file) in children {
+                let Some(&j) = positions.get(&file) else {
+                    continue;
+                };
+                if placed[j].is_none() {
+                    placed[j] = Some((krate, [module.as_slice(), &inner].concat()));
+                    worklist.push(j);
+                }
+            }
+        }
+    }
+
+    // Files outside any module tree: the crate whose root directory holds
+    // them, else one crate per `src` directory
+    let mut loose: HashMap<PathBuf, usize> = HashMap::new();
+    let mut next = roots.len();
+    files
+        .iter()
+        .zip(placed)
+        .map(|((path, syntax), placed)| {
+            let (krate, module) = placed.unwrap_or_else(|| {
+                let file = Path::new(path);
+                let owner = roots
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &r)| {
+                        Path::new(&files[r].0)
+                            .parent()
+                            .is_some_and(|dir| file.starts_with(dir))
+                    })
+                    .max_by_key(|(_, &r)| files[r].0.len())
+                    .map(|(krate, _)| krate);
+                let krate = owner.unwrap_or_else(|| {
+                    *loose.entry(src_dir(file)).or_insert_with(|| {
+                        next += 1;
+                        next - 1
+                    })
+                });
+                (krate, module_path(file))
+            });
+            IndexedFile {
+                path: path.clone(),
+                syntax,
+                krate,
+                module,
+            }
+        })
+        .collect()
+}
+
+/// The directory `mod x;` declarations in `path` look in: the file's own
+/// directory for `lib.rs`, `main.rs` and `mod.rs`, else a directory named
+/// after the file.
+fn child_dir(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or(Path::new(""));
+    match path.file_stem().and_then(|s| s.to_str()) {
+        Some("lib" | "main" | "mod") | None => parent.to_path_buf(),
+        Some(stem) => parent.join(stem),
+    }
+}
+
+/// The files the `mod x;` declarations among `items` load, with the module
+/// path of each relative to the declaring file.
+fn declared_mods(
+    items: &[Item],
+    dir: &Path,
+    file_dir: Option<&Path>,
+    inline: &mut Vec<String>,
+    out: &mut Vec<(Vec<String>, PathBuf)>,
+) {
+    for item in items {
+        let Item::Mod(m) = item else {
+            continue;
+        };
+        let name = m.ident.to_string();
+        let explicit = m.attrs.iter().find_map(|a| match &a.meta {
+            syn::Meta::NameValue(nv) if nv.path.is_ident("path") => match &nv.value {
+                Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(s),
+                    ..
+                }) => Some(s.value()),
+                _ => None,
+            },
+            _ => None,
+        });
+        let mut module = inline.clone();
+        module.push(name.clone());
+        match &m.content {
+            Some((_, items)) => {
+                // `#[path]` on an inline module names the directory of its
+                // children
+                let dir = match explicit {
+                    Some(p) => dir.join(p),
+                    None => dir.join(&name),
+                };
+                inline.push(name);
+                declared_mods(items, &dir, None, inline, out);
+                inline.pop();
+            }
+            None => {
+                let file = match explicit {
+                    // Relative to the declaring file at the top level, to
+                    // the module directory inside inline modules
+                    Some(p) => file_dir.unwrap_or(dir).join(p),
+                    None if dir.join(&name).join("mod.rs").exists() => {
+                        dir.join(&name).join("mod.rs")
+                    }
+                    None => dir.join(format!("{}.rs", name)),
+                };
+                out.push((module, file));
+            }
+        }
+    }
+}
+
+/// The `src` directory holding `path`, or its parent directory.
+fn src_dir(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|a| a.file_name().is_some_and(|n| n == "src"))
+        .or(path.parent())
+        .unwrap_or(Path::new(""))
+        .to_path_buf()
+}
+
+/// The module a source file stands for, from its path below the nearest
+/// `src` directory. `lib.rs`, `main.rs` and `mod.rs` stand for their
+/// directory.
+fn module_path(path: &Path) -> Vec<String> {
+    let components: Vec<String> = path
+        .with_extension("")
+        .iter()
+        .map(|c| c.to_string_lossy().to_string())
+        .collect();
+    let start = components
+        .iter()
+        .rposition(|c| c == "src")
+        .map_or(0, |i| i + 1);
+    let mut module = components[start..].to_vec();
+    if module
+        .last()
+        .is_some_and(|m| matches!(m.as_str(), "lib" | "main" | "mod"))
+    {
+        module.pop();
+    }
+    module
+}
+
+/// Collects the names a `use` tree brings into scope, with the path each
+/// stands for. Globs are recorded without a name.
+#[derive(Default)]
+struct UseCollector {
+    prefix: Vec<String>,
+    imports: Vec<(Option<String>, Vec<String>)>,
+}
+
+impl UseCollector {
+    fn collect(&mut self, tree: &syn::UseTree) {
+        match tree {
+            syn::UseTree::Path(p) => {
+                self.prefix.push(p.ident.to_string());
+                self.collect(&p.tree);
+                self.prefix.pop();
+            }
+            syn::UseTree::Name(n) => self.import(n.ident.to_string(), n.ident.to_string()),
+            syn::UseTree::Rename(r) => self.import(r.rename.to_string(), r.ident.to_string()),
+            syn::UseTree::Group(g) => g.items.iter().for_each(|t| self.collect(t)),
+            syn::UseTree::Glob(_) => self.imports.push((None, self.prefix.clone())),
+        }
+    }
+
+    fn import(&mut self, alias: String, name: String) {
+        // `use foo::{self}` names the module itself
+        let mut path = self.prefix.clone();
+        if name != "self" {
+            path.push(name);
+        }
+        let alias = match alias.as_str() {
+            "self" => path.last().cloned().unwrap_or(alias),
+            _ => alias,
+        };
+        self.imports.push((Some(alias), path));
+    }
+}
+
+/// A call found in a body: a path call or a method call, with its
+/// arguments (the receiver first for methods).
+enum Call<'ast> {
+    Path(&'ast syn::Path, Vec<&'ast Expr>),
+    Method(String, Vec<&'ast Expr>),
+}
+
+#[derive(Default)]
+struct CallFinder<'ast> {
+    calls: Vec<(usize, Call<'ast>)>,
+}
+
+impl<'ast> Visit<'ast> for CallFinder<'ast> {
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        if let Expr::Path(p) = &*node.func {
+            self.calls.push((
+                node.func.span().start().line,
+                Call::Path(&p.path, node.args.iter().collect()),
+            ));
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        self.calls.push((
+            node.method.span().start().line,
+            Call::Method(
+                node.method.to_string(),
+                std::iter::once(&*node.receiver).chain(&node.args).collect(),
+            ),
+        ));
+        syn::visit::visit_expr_method_call(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CrateIndex;
+
+    /// The resolved calls of `source`, as qualified caller and callee names.
+    fn calls(source: &str) -> Vec<(String, String)> {
+        let syntax = syn::parse_file(source).expect("test source parses");
+        let index = CrateIndex::build(&[("src/lib.rs".to_string(), &syntax)], 3);
+        index
+            .calls
+            .iter()
+            .map(|c| {
+                (
+                    index.fns[c.caller].item.name.clone(),
+                    index.fns[c.callee].item.name.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn imported_function_is_not_a_local_method() {
+        let calls = calls(
+            r#"
+            use anchor_spl::token::transfer;
+
+            impl<'info> Withdraw<'info> {
+                fn transfer(&self, amount: u64) {}
+            }
+
+            pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+                transfer(cpi, amount)?;
+                Ok(())
+            }
+            "#,
+        );
+        assert!(calls.is_empty(), "{calls:?}");
+    }
+
+    #[test]
+    fn methods_and_inline_modules_are_scoped() {
+        let calls = calls(
+            r#"
+            mod a {
+                pub fn helper() {}
+            }
+
+            mod b {
+                pub fn helper() {}
+
+                pub fn run() {
+                    helper();
+                }
+            }
+
+            impl Vault {
+                fn check(&self) {}
+
+                fn run(&self) {
+                    Self::check(self);
+                }
+            }
+
+            pub fn handler(vault: &Vault) {
+                Vault::check(vault);
+                a::helper();
+            }
+            "#,
+        );
+        let expected = [
+            ("b::run", "b::helper"),
+            ("Vault::run", "Vault::check"),
+            ("handler", "Vault::check"),
+            ("handler", "a::helper"),
+        ];
+        assert_eq!(calls, expected.map(|(a, b)| (a.to_string(), b.to_string())));
+    }
+}
@@ -1,6 +1,21 @@
 pub mod accounts;
+pub mod arithmetic;
+pub mod cast;
+pub mod close;
+pub mod constraints;
+pub mod context;
 pub mod cpi;
+pub mod index;
+pub mod mutation;
+pub mod owner;
+pub mod panic;
 pub mod pda;
+pub mod precision;
+pub mod program;
+pub mod remaining;
+pub mod seeds;
+pub mod types;
+pub mod workspace;
 
 use serde::Serialize;
 use std::path::{Path, PathBuf};
@@ -31,22 +46,42 @@ pub struct Finding {
     pub message: String,
     pub file: String,
     pub line: usize,
+    /// Qualified name of the enclosing function, e.g. `vault::Withdraw::transfer`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
 }
 
+/// The findings of one program crate.
 #[derive(Debug, Serialize)]
-pub struct AnalysisReport {
+pub struct ProgramReport {
+    pub name: String,
+    pub path: String,
     pub findings: Vec<Finding>,
     pub files_scanned: usize,
 }
 
+#[derive(Debug, Serialize)]
+pub struct AnalysisReport {
+    pub programs: Vec<ProgramReport>,
+    pub files_scanned: usize,
+}
+
 impl AnalysisReport {
+    /// Every finding, program by program.
+    pub fn findings(&self) -> impl Iterator<Item = &Finding> {
+        self.programs.iter().flat_map(|p| &p.findings)
+    }
+
+    pub fn finding_count(&self) -> usize {
+        self.programs.iter().map(|p| p.findings.len()).sum()
+    }
+
     pub fn has_high(&self) -> bool {
-        self.findings.iter().any(|f| f.severity == Severity::High)
+        self.findings().any(|f| f.severity == Severity::High)
     }
 
     pub fn has_medium_or_above(&self) -> bool {
-        self.findings
-            .iter()
+        self.findings()
             .any(|f| f.severity == Severity::High || f.severity == Severity::Medium)
     }
 }
@@ -57,18 +92,35 @@ pub fn discover_rust_files(root: &Path) -> Vec<PathBuf> {
         .into_iter()
         .filter_map(|e| e.ok())
         .filter(|e| {
-            e.path().extension().map_or(false, |ext| ext == "rs")
+            e.path().extension().is_some_and(|ext| ext == "rs")
                 && !e.path().to_string_lossy().contains("/target/")
         })
         .map(|e| e.into_path())
         .collect()
 }
 
-/// Run all checks against a set of Rust source files.
-pub fn analyze(files: &[PathBuf]) -> AnalysisReport {
-    let mut findings = Vec::new();
+/// Run all checks against each program, one crate at a time, following
+/// calls into local functions `call_depth` deep.
+pub fn analyze(programs: &[workspace::Program], call_depth: usize) -> AnalysisReport {
+    let programs: Vec<ProgramReport> = programs
+        .iter()
+        .map(|program| analyze_crate(program, call_depth))
+        .collect();
+
+    AnalysisReport {
+        files_scanned: programs.iter().map(|p| p.files_scanned).sum(),
+        programs,
+    }
+}
+
+/// Run all checks against the source files of one crate. Files that cannot
+/// be read or parsed are skipped and not counted as scanned.
+fn analyze_crate(program: &workspace::Program, call_depth: usize) -> ProgramReport {
+    let overflow_checks = arithmetic::release_overflow_checks(program);
 
-    for file in files {
+    // Parse everything up front so checks can look across files
+    let mut parsed = Vec::new();
+    for file in &program.files {
         let source = match std::fs::read_to_string(file) {
             Ok(s) => s,
             Err(_) => continue,
@@ -79,17 +131,84 @@ pub fn analyze(files: &[PathBuf]) -> AnalysisReport {
             Err(_) => continue,
         };
 
-        let file_str = file.to_string_lossy().to_string();
+        parsed.push((file.to_string_lossy().to_string(), source, syntax));
+    }
 
-        findings.extend(accounts::check_account_validation(&syntax, &file_str, &source));
-        findings.extend(cpi::check_cpi_safety(&syntax, &file_str, &source));
-        findings.extend(pda::check_pda_usage(&syntax, &file_str, &source));
+    ProgramReport {
+        name: program.name.clone(),
+        path: program.root.to_string_lossy().to_string(),
+        findings: check_crate(&parsed, call_depth, overflow_checks),
+        files_scanned: parsed.len(),
     }
+}
 
-    findings.sort_by(|a, b| a.severity.cmp(&b.severity));
+/// Run all checks against the parsed `(path, source, syntax)` files of one
+/// crate.
+fn check_crate(
+    parsed: &[(String, String, syn::File)],
+    call_depth: usize,
+    overflow_checks: bool,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut seed_shapes = Vec::new();
 
-    AnalysisReport {
-        files_scanned: files.len(),
-        findings,
+    let index = index::CrateIndex::build(
+        &parsed
+            .iter()
+            .map(|(path, _, syntax)| (path.clone(), syntax))
+            .collect::<Vec<_>>(),
+        call_depth,
+    );
+    let model = program::ProgramModel::build(&index);
+    let bumps = pda::StoredBumps::collect(&index, &model);
+
+    for (file_str, source, syntax) in parsed {
+        let (file_str, source) = (file_str.as_str(), source.as_str());
+
+        findings.extend(accounts::check_account_validation(
+            syntax, file_str, source, &model, &index,
+        ));
+        findings.extend(cpi::check_cpi_safety(
+            syntax, file_str, &model, &index, &bumps,
+        ));
+        findings.extend(pda::check_pda_usage(
+            syntax, file_str, &model, &index, &bumps,
+        ));
+        findings.extend(owner::check_owner_validation(file_str, &model));
+        findings.extend(close::check_account_closing(
+            syntax, file_str, &model, &index,
+        ));
+        findings.extend(remaining::check_remaining_accounts(
+            syntax, file_str, &index,
+        ));
+        findings.extend(mutation::check_account_mutation(file_str, &model, &index));
+        seed_shapes.extend(pda::collect_seed_shapes(syntax, file_str, &index));
+        findings.extend(arithmetic::check_arithmetic(
+            file_str,
+            &index,
+            overflow_checks,
+        ));
+        findings.extend(precision::check_precision(file_str, &index));
+        findings.extend(cast::check_casts(file_str, &index));
+        findings.extend(panic::check_panics(file_str, &index));
     }
+
+    // Seed collisions are a property of the whole program, not of one file
+    let seed_consts = seeds::collect_seed_consts(&index);
+    findings.extend(seeds::check_seed_collisions(&seed_shapes, &seed_consts));
+
+    findings.sort_by_key(|f| f.severity);
+    findings
+}
+
+/// The `check` findings for `source`, analyzed as the `src/lib.rs` of a
+/// crate on its own.
+#[cfg(test)]
+pub(crate) fn check_source(source: &str, check: &str) -> Vec<Finding> {
+    let syntax = syn::parse_file(source).expect("test source parses");
+    let parsed = [("src/lib.rs".to_string(), source.to_string(), syntax)];
+    check_crate(&parsed, 3, false)
+        .into_iter()
+        .filter(|f| f.check == check)
+        .collect()
 }
@@ -0,0 +1,325 @@
+use super::constraints::{AccountField, AccountsStruct, ConstraintKind};
+use super::context::{compares_key, tokens_contain, touches_lamports, write_sites, FnContext};
+use super::index::CrateIndex;
+use super::pda::TRACE_DEPTH;
+use super::program::ProgramModel;
+use super::remaining::MUT_BORROWS;
+use super::{Finding, Severity};
+use syn::visit::Visit;
+use syn::Expr;
+
+/// Methods that change an account's lamports or data in place, besides the
+/// mutable borrows and `set_inner`.
+const MUTATING_CALLS: &[&str] = &["add_lamports", "sub_lamports", "set_lamports", "load_mut"];
+
+/// Calls that give another handle on the same account, e.g.
+/// `ctx.accounts.vault.to_account_info()`.
+const ACCOUNT_VIEWS: &[&str] = &[
+    "to_account_info",
+    "as_mut",
+    "as_ref",
+    "deref_mut",
+    "load_mut",
+    "try_borrow_mut_data",
+    "try_borrow_mut_lamports",
+    "borrow_mut",
+];
+
+/// Checks, for every handler and the methods of its accounts struct:
+/// 1. Accounts written to (fields, lamports, data) that are not marked
+///    `mut`, `init`, `init_if_needed` or `zero`
+/// 2. `mut` accounts written to while nothing ties them to the caller: no
+///    validating constraint, no other field's constraint naming them and no
+///    key comparison in the handler or the helpers it calls
+pub fn check_account_mutation(
+    path: &str,
+    model: &ProgramModel,
+    index: &CrateIndex,
+) -> Vec<Finding> {
+    let resolved: Vec<(&FnContext, &AccountsStruct)> = index
+        .fns_in(path)
+        .filter_map(|f| Some((f, model.accounts_for(f, path)?)))
+        .collect();
+    let mut findings = Vec::new();
+
+    let mut seen: Vec<&AccountsStruct> = Vec::new();
+    for &(_, accounts) in &resolved {
+        if seen.iter().any(|s| std::ptr::eq(*s, accounts)) {
+            continue;
+        }
+        seen.push(accounts);
+        let bound: Vec<&FnContext> = resolved
+            .iter()
+            .filter(|(_, a)| std::ptr::eq(*a, accounts))
+            .map(|(f, _)| *f)
+            .collect();
+
+        // Every write, resolved to the account it goes through
+        let mut writes: Vec<(&str, usize, &FnContext, bool)> = Vec::new();
+        for func in &bound {
+            let mut sites = MutatingCalls { sites: Vec::new() };
+            func.visit(&mut sites);
+            sites
+                .sites
+                .extend(write_sites(func).into_iter().map(|site| {
+                    let credit = matches!(site.op, Some(syn::BinOp::AddAssign(_)))
+                        && touches_lamports(site.target);
+                    (site.line, site.target, credit)
+                }));
+            for (line, target, credit) in sites.sites {
+                if let Some(field) = written_account(func, accounts.fields.as_slice(), target) {
+                    writes.push((field, line, func, credit));
+                }
+            }
+        }
+
+        for field in &accounts.fields {
+            let Some(&(_, line, func, _)) = writes.iter().find(|(f, ..)| *f == field.name) else {
+                continue;
+            };
+            if matches!(field.type_name().as_str(), "Program" | "Sysvar") {
+                continue;
+            }
+
+            let created = field.has(ConstraintKind::Init)
+                || field.has(ConstraintKind::InitIfNeeded)
+                || field.has(ConstraintKind::Zero);
+            if !created && !field.has(ConstraintKind::Mut) {
+                findings.push(Finding {
+                    severity: Severity::Medium,
+                    check: "account-mutation".into(),
+                    message: format!(
+                        "`{}` writes to `{}`, which `{}` does not mark `mut`. Anchor does not \
+                         persist changes to read-only accounts and lamport changes fail at \
+                         runtime. Add `#[account(mut)]`.",
+                        func.simple_name(),
+                        field.name,
+                        accounts.name
+                    ),
+                    file: path.to_string(),
+                    line,
+                    context: Some(func.name.clone()),
+                });
+                continue;
+            }
+
+            // A signer authorizes changes to its own account
+            let guarded = created
+                || field.type_name() == "Signer"
+                || field.is_validated()
+                || is_named_by_constraint(field, &accounts.fields)
+                || bound.iter().any(|f| compares_key(f, &field.name))
+                || model
+                    .handlers_of(accounts)
+                    .iter()
+                    .any(|h| compares_key(&h.func, &field.name));
+            // Paying lamports into an account needs no say from its owner
+            let debit = writes
+                .iter()
+                .find(|(f, _, _, credit)| *f == field.name && !credit);
+            let Some(&(_, line, func, _)) = debit.filter(|_| !guarded) else {
+                continue;
+            };
+            let (severity, why) = if field.is_raw() {
+                (
+                    Severity::High,
+                    "any account, owned by any program, can be passed in its place",
+                )
+            } else {
+                (
+                    Severity::Medium,
+                    "any account of the right type can be passed in its place, including one \
+                     belonging to another user",
+                )
+            };
+            findings.push(Finding {
+                severity,
+                check: "account-mutation".into(),
+                message: format!(
+                    "`{}` mutates `{}` in `{}`, but nothing ties the account to the caller: \
+                     {}. Add `has_one`, `seeds`, `address`, `owner` or a `constraint` to `{}`.",
+                    func.simple_name(),
+                    field.name,
+                    accounts.name,
+                    why,
+                    field.name
+                ),
+                file: path.to_string(),
+                line,
+                context: Some(func.name.clone()),
+            });
+        }
+    }
+
+    findings
+}
+
+/// Whether another field's constraint refers to `field`, e.g.
+/// `has_one = vault` on a state account.
+fn is_named_by_constraint(field: &AccountField, fields: &[AccountField]) -> bool {
+    fields
+        .iter()
+        .filter(|f| f.name != field.name)
+        .flat_map(|f| &f.constraints)
+        .any(|c| {
+            c.value
+                .as_ref()
+                .is_some_and(|v| tokens_contain(v, &field.name))
+        })
+}
+
+/// The identifiers along a place expression, base first:
+/// `ctx.accounts.vault.total` gives `[ctx, accounts, vault, total]`.
+fn place_chain(expr: &Expr, out: &mut Vec<String>) {
+    match expr {
+        Expr::Field(f) => {
+            place_chain(&f.base, out);
+            if let syn::Member::Named(name) = &f.member {
+                out.push(name.to_string());
+            }
+        }
+        Expr::MethodCall(m) => {
+            place_chain(&m.receiver, out);
+            out.push(m.method.to_string());
+        }
+        Expr::Index(i) => place_chain(&i.expr, out),
+        Expr::Unary(u) => place_chain(&u.expr, out),
+        Expr::Try(t) => place_chain(&t.expr, out),
+        Expr::Paren(p) => place_chain(&p.expr, out),
+        Expr::Reference(r) => place_chain(&r.expr, out),
+        Expr::Path(p) => {
+            if let Some(ident) = p.path.get_ident() {
+                out.push(ident.to_string());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The accounts-struct field a write to `target` goes through:
+/// `ctx.accounts.x...`, `self.x...`, or a local bound to one of them
+/// (`let x = &mut ctx.accounts.x;`).
+fn written_account<'f>(
+    func: &FnContext,
+    fields: &'f [AccountField],
+    target: &Expr,
+) -> Option<&'f str> {
+    let mut chain = Vec::new();
+    place_chain(target, &mut chain);
+    account_in(func, fields, &chain, TRACE_DEPTH)
+}
+
+fn account_in<'f>(
+    func: &FnContext,
+    fields: &'f [AccountField],
+    chain: &[String],
+    depth: usize,
+) -> Option<&'f str> {
+    let name = match chain.iter().position(|s| s == "accounts") {
+        Some(i) => chain.get(i + 1)?,
+        None if chain.first()? == "self" => chain.get(1)?,
+        None if depth > 0 => {
+            // A local only stands for the account if it is bound to the
+            // account itself, not to a value read out of it
+            let init = func.local_init(chain.first()?)?;
+            let mut bound = Vec::new();
+            place_chain(init, &mut bound);
+            let name = account_in(func, fields, &bound, depth - 1)?;
+            let at = bound.iter().rposition(|s| s == name)?;
+            return bound[at + 1..]
+                .iter()
+                .all(|s| ACCOUNT_VIEWS.contains(&s.as_str()))
+                .then_some(name);
+        }
+        None => return None,
+    };
+    fields
+        .iter()
+        .find(|f| f.name == *name)
+        .map(|f| f.name.as_str())
+}
+
+/// Collects the receivers of calls that write to an account in place,
+/// flagging the ones that only add lamports. Assignments and `set_inner`
+/// come from `write_sites`.
+struct MutatingCalls<'ast> {
+    sites: Vec<(usize, &'ast Expr, bool)>,
+}
+
+impl<'ast> Visit<'ast> for MutatingCalls<'ast> {
+    fn visit_expr_method_call(&mut self, node: &'ast syn::ExprMethodCall) {
+        let method = node.method.to_string();
+        // Borrows of the lamports are recorded by the assignment around them
+        let lamports = method == "try_borrow_mut_lamports" || touches_lamports(&node.receiver);
+        if (MUT_BORROWS.contains(&method.as_str()) && !lamports)
+            || MUTATING_CALLS.contains(&method.as_str())
+        {
+            self.sites.push((
+                node.method.span().start().line,
+                &node.receiver,
+                method == "add_lamports",
+            ));
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::check_source;
+
+    #[test]
+    fn unmarked_and_untied_writes() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn settle(ctx: Context<Settle>, amount: u64) -> Result<()> {
+                    ctx.accounts.config.fee = 5;
+                    **ctx.accounts.pool.try_borrow_mut_lamports()? -= amount;
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Settle<'info> {
+                pub config: Account<'info, Config>,
+                #[account(mut)]
+                pub pool: AccountInfo<'info>,
+            }
+            "#,
+            "account-mutation",
+        );
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [6, 5], "{findings:?}");
+        assert!(findings[1].message.contains("does not mark `mut`"));
+    }
+
+    #[test]
+    fn tied_writes_and_credits() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod vault {
+                pub fn settle(ctx: Context<Settle>, amount: u64) -> Result<()> {
+                    let config = &mut ctx.accounts.config;
+                    config.fee += 5;
+                    **ctx.accounts.recipient.try_borrow_mut_lamports()? += amount;
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Settle<'info> {
+                #[account(mut, has_one = admin)]
+                pub config: Account<'info, Config>,
+                #[account(mut)]
+                pub recipient: AccountInfo<'info>,
+                pub admin: Signer<'info>,
+            }
+            "#,
+            "account-mutation",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+}
@@ -0,0 +1,248 @@
+use super::constraints::{AccountField, ConstraintKind};
+use super::context::{compares_in_macro, is_comparison, tokens_contain, Aliases, FnContext};
+use super::program::ProgramModel;
+use super::{Finding, Severity};
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{Expr, ExprCall, ExprIf, Macro};
+
+/// Calls that turn raw account bytes into a typed value without looking at
+/// who owns the account.
+pub const DESERIALIZERS: &[&str] = &[
+    "try_from_slice",
+    "deserialize",
+    "try_deserialize",
+    "try_deserialize_unchecked",
+    "try_from_unchecked",
+];
+
+/// Checks for:
+/// 1. Raw `AccountInfo` / `UncheckedAccount` fields whose data a handler
+///    deserializes without first comparing the account's owner. Both may
+///    happen in a helper the handler passes the account to
+pub fn check_owner_validation(path: &str, model: &ProgramModel) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for (handler, accounts) in model.handlers_in(path) {
+        for field in accounts.fields.iter().filter(|f| f.is_raw()) {
+            if has_owner_constraint(field) {
+                continue;
+            }
+
+            let sites = Sites::of(&handler.func, &field.name);
+            for (line, deserializer) in sites.unchecked() {
+                findings.push(Finding {
+                    severity: Severity::High,
+                    check: "missing-owner-check".into(),
+                    message: format!(
+                        "Raw account `{}` in `{}` is deserialized with {} without checking \
+                         its owner. An attacker can pass an account owned by another program \
+                         with forged data. Compare `{}.owner` against `crate::ID` (or the \
+                         expected token program) first, add an `owner = ...` constraint, or \
+                         use `Account<'info, T>`.",
+                        field.name, accounts.name, deserializer, field.name
+                    ),
+                    file: path.to_string(),
+                    line: *line,
+                    context: Some(handler.func.name.clone()),
+                });
+            }
+        }
+    }
+
+    findings
+}
+
+/// `owner = ...`, or a `constraint = ...` that looks at `.owner`.
+fn has_owner_constraint(field: &AccountField) -> bool {
+    field.constraints.iter().any(|c| match c.kind {
+        ConstraintKind::Owner => true,
+        ConstraintKind::Constraint => c
+            .value
+            .as_ref()
+            .is_some_and(|v| tokens_contain(v, ".owner")),
+        _ => false,
+    })
+}
+
+/// Where a function deserializes an account and where it compares the
+/// account's owner, in its own body or in the callees it passes the account
+/// to. A callee's sites are placed on the line of the call.
+struct Sites {
+    /// Lines with the deserializer used, e.g. `` `try_deserialize` ``.
+    deserializations: Vec<(usize, String)>,
+    owner_checks: Vec<usize>,
+}
+
+impl Sites {
+    fn of(func: &FnContext, root: &str) -> Self {
+        let aliases = func.aliases(root);
+        let mut finder = SiteFinder {
+            aliases: &aliases,
+            deserializations: Vec::new(),
+            owner_checks: Vec::new(),
+        };
+        func.visit(&mut finder);
+        let mut sites = Sites {
+            deserializations: finder
+                .deserializations
+                .into_iter()
+                .map(|(line, name)| (line, format!("`{}`", name)))
+                .collect(),
+            owner_checks: finder.owner_checks,
+        };
+
+        for callee in &func.callees {
+            let mut params = callee.params_for(root);
+            params.extend(callee.bindings.iter().filter_map(|(param, arg)| {
+                arg.iter()
+                    .any(|t| aliases.locals.contains(t))
+                    .then_some(param.as_str())
+            }));
+            for param in params {
+                let inner = Sites::of(&callee.func, param);
+                let unchecked = inner.unchecked().next();
+                if let Some((_, deserializer)) = unchecked {
+                    sites.deserializations.push((
+                        callee.line,
+                        format!("{} in `{}`", deserializer, callee.func.simple_name()),
+                    ));
+                } else if !inner.owner_checks.is_empty() {
+                    sites.owner_checks.push(callee.line);
+                }
+            }
+        }
+        sites
+    }
+
+    /// The deserializations no earlier owner comparison protects: an owner
+    /// comparison only covers reads that come after it.
+    fn unchecked(&self) -> impl Iterator<Item = &(usize, String)> {
+        self.deserializations
+            .iter()
+            .filter(|(line, _)| !self.owner_checks.iter().any(|l| l <= line))
+    }
+}
+
+/// Collects, for one account and its local aliases, the lines where its data
+/// is deserialized and the lines where its owner is compared.
+struct SiteFinder<'n> {
+    aliases: &'n Aliases,
+    deserializations: Vec<(usize, String)>,
+    owner_checks: Vec<usize>,
+}
+
+impl<'n> SiteFinder<'n> {
+    fn record_owner_check(&mut self, node: &impl quote::ToTokens, line: usize) {
+        if tokens_contain(node, ".owner") && self.aliases.mentioned_by(node) {
+            self.owner_checks.push(line);
+        }
+    }
+}
+
+impl<'n, 'ast> Visit<'ast> for SiteFinder<'n> {
+    fn visit_expr_call(&mut self, node: &'ast ExprCall) {
+        if let Expr::Path(p) = &*node.func {
+            if let Some(last) = p.path.segments.last() {
+                let name = last.ident.to_string();
+                if DESERIALIZERS.contains(&name.as_str())
+                    && node.args.iter().any(|a| self.aliases.mentioned_by(a))
+                {
+                    self.deserializations
+                        .push((node.func.span().start().line, name));
+                }
+            }
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_if(&mut self, node: &'ast ExprIf) {
+        if is_comparison(&node.cond) {
+            self.record_owner_check(&node.cond, node.if_token.span.start().line);
+        }
+        syn::visit::visit_expr_if(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        if compares_in_macro(node) {
+            self.record_owner_check(&node.tokens, node.path.span().start().line);
+        }
+        syn::visit::visit_macro(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::check_source;
+
+    fn read_config(body: &str) -> String {
+        format!(
+            r#"
+            #[program]
+            pub mod reader {{
+                use super::*;
+                pub fn read(ctx: Context<Read>) -> Result<()> {{
+                    {body}
+                    Ok(())
+                }}
+            }}
+
+            fn load(acc: &AccountInfo) -> Result<Config> {{
+                Config::try_deserialize(&mut &acc.data.borrow()[..])
+            }}
+
+            #[derive(Accounts)]
+            pub struct Read<'info> {{
+                /// CHECK: read manually
+                pub config: AccountInfo<'info>,
+            }}
+            "#
+        )
+    }
+
+    #[test]
+    fn unchecked_deserialization() {
+        let source = read_config(
+            "let data = ctx.accounts.config.try_borrow_data()?;
+             let cfg = Config::try_deserialize(&mut &data[..])?;",
+        );
+        assert_eq!(check_source(&source, "missing-owner-check").len(), 1);
+    }
+
+    #[test]
+    fn logging_the_owner_is_not_a_check() {
+        let source = read_config(
+            r#"msg!("owner {}", ctx.accounts.config.owner);
+               let data = ctx.accounts.config.try_borrow_data()?;
+               let cfg = Config::try_deserialize(&mut &data[..])?;"#,
+        );
+        assert_eq!(check_source(&source, "missing-owner-check").len(), 1);
+    }
+
+    #[test]
+    fn owner_compared_first() {
+        for check in [
+            "require_keys_eq!(*ctx.accounts.config.owner, crate::ID);",
+            "require!(ctx.accounts.config.owner == &crate::ID, E::Owner);",
+            "if ctx.accounts.config.owner != &crate::ID { return err!(E::Owner); }",
+        ] {
+            let source = read_config(&format!(
+                "{check}
+                 let data = ctx.accounts.config.try_borrow_data()?;
+                 let cfg = Config::try_deserialize(&mut &data[..])?;"
+            ));
+            assert!(
+                check_source(&source, "missing-owner-check").is_empty(),
+                "{check}"
+            );
+        }
+    }
+
+    #[test]
+    fn deserialization_in_helper() {
+        let source = read_config("let cfg = load(&ctx.accounts.config)?;");
+        let findings = check_source(&source, "missing-owner-check");
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].message.contains("in `load`"));
+    }
+}
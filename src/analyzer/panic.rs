@@ -0,0 +1,316 @@
+use super::context::{compact_tokens, FnContext};
+use super::index::CrateIndex;
+use super::types::{is_constant, primitive};
+use super::{Finding, Severity};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{Expr, ExprIndex, ExprMethodCall};
+
+/// How many constants deep a length such as `HEADER + SIZE` is evaluated.
+const CONST_DEPTH: usize = 4;
+
+/// Macros that abort the program unconditionally.
+const PANIC_MACROS: &[&str] = &["panic", "unreachable", "todo", "unimplemented"];
+
+/// Checks for, inside `#[program]` functions and the helpers they call:
+/// 1. `unwrap()` and `expect()`
+/// 2. Indexing and slicing (`data[0]`, `data[..8]`)
+/// 3. `panic!`, `unreachable!`, `todo!` and `unimplemented!`
+///
+/// `slice[a..b].try_into().unwrap()` is allowed when the range has the
+/// length of the `[T; N]` it is converted into, as given by a `let`
+/// annotation or a `u64::from_le_bytes`-style call, since the conversion
+/// then cannot fail.
+pub fn check_panics(path: &str, index: &CrateIndex) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for func in index.program_fns(path) {
+        let mut visitor = PanicVisitor {
+            path,
+            func,
+            index,
+            sized: Vec::new(),
+            findings: Vec::new(),
+        };
+        func.visit(&mut visitor);
+        findings.extend(visitor.findings);
+    }
+
+    findings
+}
+
+/// The value of a constant expression: integer literals, constants of the
+/// crate and `size_of::<T>()` of primitives, combined with `+`, `-` and `*`.
+fn const_value(expr: &Expr, index: &CrateIndex, depth: usize) -> Option<u64> {
+    match expr {
+        Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(i),
+            ..
+        }) => i.base10_parse().ok(),
+        Expr::Paren(p) => const_value(&p.expr, index, depth),
+        Expr::Binary(b) => {
+            let (l, r) = (
+                const_value(&b.left, index, depth)?,
+                const_value(&b.right, index, depth)?,
+            );
+            match b.op {
+                syn::BinOp::Add(_) => l.checked_add(r),
+                syn::BinOp::Sub(_) => l.checked_sub(r),
+                syn::BinOp::Mul(_) => l.checked_mul(r),
+                _ => None,
+            }
+        }
+        Expr::Path(p) if depth > 0 => {
+            let name = p.path.segments.last()?.ident.to_string();
+            let item = index.consts.iter().find(|c| c.name == name)?.item;
+            const_value(&item.expr, index, depth - 1)
+        }
+        Expr::Call(c) => {
+            let Expr::Path(p) = &*c.func else {
+                return None;
+            };
+            let last = p.path.segments.last()?;
+            if last.ident != "size_of" {
+                return None;
+            }
+            let syn::PathArguments::AngleBracketed(args) = &last.arguments else {
+                return None;
+            };
+            match args.args.first()? {
+                syn::GenericArgument::Type(ty) => byte_width(&primitive(ty)?),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The size in bytes of a primitive numeric type.
+fn byte_width(primitive: &str) -> Option<u64> {
+    match primitive {
+        "u8" | "i8" => Some(1),
+        "u16" | "i16" => Some(2),
+        "u32" | "i32" | "f32" => Some(4),
+        "u64" | "i64" | "f64" | "usize" | "isize" => Some(8),
+        "u128" | "i128" => Some(16),
+        _ => None,
+    }
+}
+
+/// A length to compare: its value when it can be evaluated, else its
+/// tokens, so that `..LEN` still matches `[u8; LEN]`.
+fn length(expr: &Expr, index: &CrateIndex) -> String {
+    const_value(expr, index, CONST_DEPTH)
+        .map_or_else(|| compact_tokens(expr.to_token_stream()), |n| n.to_string())
+}
+
+/// The number of elements `range` spans, when fixed at compile time:
+/// `..8`, `8..16`, `offset..offset + 8`.
+fn range_length(range: &syn::ExprRange, index: &CrateIndex) -> Option<String> {
+    match (range.start.as_deref(), range.end.as_deref()) {
+        (None, Some(end)) => Some(length(end, index)),
+        (Some(start), Some(end)) if is_constant(start) && is_constant(end) => {
+            let start = const_value(start, index, CONST_DEPTH)?;
+            let end = const_value(end, index, CONST_DEPTH)?;
+            end.checked_sub(start).map(|n| n.to_string())
+        }
+        (Some(start), Some(Expr::Binary(b)))
+            if matches!(b.op, syn::BinOp::Add(_))
+                && is_constant(&b.right)
+                && b.left.to_token_stream().to_string() == start.to_token_stream().to_string() =>
+        {
+            Some(length(&b.right, index))
+        }
+        _ => None,
+    }
+}
+
+/// The length of `expr` when it is a fixed-size array or a constant-length
+/// slice of one, looking through `&` and parentheses.
+fn fixed_len(expr: &Expr, index: &CrateIndex) -> Option<String> {
+    match expr {
+        Expr::Reference(r) => fixed_len(&r.expr, index),
+        Expr::Paren(p) => fixed_len(&p.expr, index),
+        Expr::Array(a) => Some(a.elems.len().to_string()),
+        Expr::Repeat(r) => Some(length(&r.len, index)),
+        Expr::Index(i) => match &*i.index {
+            Expr::Range(r) => range_length(r, index),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+struct PanicVisitor<'v, 'ast> {
+    path: &'v str,
+    func: &'v FnContext<'ast>,
+    index: &'v CrateIndex<'ast>,
+    /// `unwrap`/`expect` calls whose value must be an array, with its
+    /// length: `let x: [u8; 8] = ...` or `u64::from_le_bytes(...)`.
+    sized: Vec<(&'ast ExprMethodCall, String)>,
+    findings: Vec<Finding>,
+}
+
+impl<'v, 'ast> PanicVisitor<'v, 'ast> {
+    fn report(&mut self, line: usize, message: String) {
+        self.findings.push(Finding {
+            severity: Severity::Low,
+            check: "panic-in-handler".into(),
+            message,
+            file: self.path.to_string(),
+            line,
+            context: Some(self.func.name.clone()),
+        });
+    }
+}
+
+impl<'v, 'ast> Visit<'ast> for PanicVisitor<'v, 'ast> {
+    fn visit_local(&mut self, node: &'ast syn::Local) {
+        if let (syn::Pat::Type(pt), Some(init)) = (&node.pat, &node.init) {
+            if let (syn::Type::Array(array), Expr::MethodCall(call)) = (&*pt.ty, &*init.expr) {
+                self.sized.push((call, length(&array.len, self.index)));
+            }
+        }
+        syn::visit::visit_local(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        // `u64::from_le_bytes(..)` takes `[u8; 8]`
+        if let (Expr::Path(p), Some(Expr::MethodCall(call))) = (&*node.func, node.args.first()) {
+            let segments: Vec<String> = p
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            let width = match segments.as_slice() {
+                [.., ty, f] if f.ends_with("_bytes") && f.starts_with("from_") => byte_width(ty),
+                [.., ty, f] if ty == "Pubkey" && f == "new_from_array" => Some(32),
+                _ => None,
+            };
+            if let Some(width) = width {
+                self.sized.push((call, width.to_string()));
+            }
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        let method = node.method.to_string();
+        if method == "unwrap" || method == "expect" {
+            let target = self
+                .sized
+                .iter()
+                .find(|(call, _)| std::ptr::eq(*call, node))
+                .map(|(_, len)| len);
+            let allowed = match &*node.receiver {
+                Expr::MethodCall(m) if m.method == "try_into" => target
+                    .is_some_and(|len| fixed_len(&m.receiver, self.index).as_ref() == Some(len)),
+                _ => false,
+            };
+            if !allowed {
+                self.report(
+                    node.method.span().start().line,
+                    format!(
+                        "`{}` in `{}` aborts the transaction with an opaque error when it \
+                         fails. Return an error instead, e.g. `.ok_or(ErrorCode::...)?`.",
+                        compact_tokens(node.to_token_stream()),
+                        self.func.simple_name()
+                    ),
+                );
+            }
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_index(&mut self, node: &'ast ExprIndex) {
+        // `data[..]` always succeeds
+        let full_range =
+            matches!(&*node.index, Expr::Range(r) if r.start.is_none() && r.end.is_none());
+        if !full_range {
+            self.report(
+                node.bracket_token.span.open().start().line,
+                format!(
+                    "`{}` in `{}` panics when out of bounds. Check the length first and \
+                     return an error, or use `.get(..)`.",
+                    compact_tokens(node.to_token_stream()),
+                    self.func.simple_name()
+                ),
+            );
+        }
+        syn::visit::visit_expr_index(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        if let Some(name) = node.path.segments.last().map(|s| s.ident.to_string()) {
+            if PANIC_MACROS.contains(&name.as_str()) {
+                self.report(
+                    node.path.span().start().line,
+                    format!(
+                        "`{}!` in `{}` aborts the transaction with an opaque error. Return an \
+                         error with `err!` or `require!` instead.",
+                        name,
+                        self.func.simple_name()
+                    ),
+                );
+            }
+        }
+        syn::visit::visit_macro(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::check_source;
+
+    /// The lines of the `unwrap` findings, leaving out the slicing ones.
+    fn unwrap_lines(source: &str) -> Vec<usize> {
+        check_source(source, "panic-in-handler")
+            .iter()
+            .filter(|f| f.message.contains("unwrap"))
+            .map(|f| f.line)
+            .collect()
+    }
+
+    #[test]
+    fn conversion_of_the_wrong_length() {
+        let lines = unwrap_lines(
+            r#"
+            #[program]
+            pub mod reader {
+                pub fn read(ctx: Context<Read>, data: Vec<u8>) -> Result<()> {
+                    let tag: [u8; 8] = data[0..4].try_into().unwrap();
+                    let amount = u64::from_le_bytes(data[..4].try_into().unwrap());
+                    let key = parse(data[..32].try_into().unwrap());
+                    let owner = ctx.accounts.owner.unwrap();
+                    Ok(())
+                }
+            }
+            "#,
+        );
+        assert_eq!(lines, [5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn conversion_of_the_right_length() {
+        let lines = unwrap_lines(
+            r#"
+            const LEN: usize = 8;
+
+            #[program]
+            pub mod reader {
+                pub fn read(ctx: Context<Read>, data: Vec<u8>, off: usize) -> Result<()> {
+                    let tag: [u8; 8] = data[0..8].try_into().unwrap();
+                    let amount = u64::from_le_bytes(data[8..16].try_into().unwrap());
+                    let key: [u8; 32] = data[off..off + 32].try_into().unwrap();
+                    let head: [u8; LEN] = data[..LEN].try_into().unwrap();
+                    let pubkey = Pubkey::new_from_array(data[..4 * LEN].try_into().unwrap());
+                    Ok(())
+                }
+            }
+            "#,
+        );
+        assert!(lines.is_empty(), "{lines:?}");
+    }
+}
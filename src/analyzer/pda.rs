@@ -1,48 +1,102 @@
+use super::constraints::{collect_accounts_structs, AccountsStruct, ConstraintKind};
+use super::context::{collect_fns, compact_tokens, tokens_contain, track_scope, FnContext, Scope};
+use super::index::CrateIndex;
+use super::program::ProgramModel;
+use super::seeds::SeedShape;
+use super::types::FieldTypes;
 use super::{Finding, Severity};
+use quote::ToTokens;
 use syn::spanned::Spanned;
 use syn::visit::Visit;
-use syn::{Expr, ExprCall, File, ItemFn};
+use syn::{Expr, ExprCall, File};
+
+/// How many `let` bindings are followed when tracing a value back to where
+/// it comes from: a bump or seed list, an account field, a program id.
+pub const TRACE_DEPTH: usize = 4;
 
 /// Checks for:
 /// 1. find_program_address / create_program_address calls where the result
 ///    is not verified against the expected program_id
-/// 2. PDA derivation using potentially user-controlled seeds without validation
-pub fn check_pda_usage(file: &File, path: &str, source: &str) -> Vec<Finding> {
+/// 2. PDA derivation with seeds the caller controls: unchecked instruction
+///    arguments and keys of unvalidated accounts
+/// 3. Bumps taken from instruction data instead of the canonical bump, in
+///    `seeds` constraints and `create_program_address` calls
+pub fn check_pda_usage<'ast>(
+    file: &'ast File,
+    path: &str,
+    model: &ProgramModel<'ast>,
+    index: &CrateIndex<'ast>,
+    bumps: &StoredBumps,
+) -> Vec<Finding> {
     let mut visitor = PdaVisitor {
         path: path.to_string(),
-        source,
         findings: Vec::new(),
-        current_fn: None,
+        scope: Scope::in_module(index.module(path)),
+        bumps,
+        model,
+        index,
     };
     visitor.visit_file(file);
+
+    for accounts in &collect_accounts_structs(file) {
+        for field in &accounts.fields {
+            if !field.has(ConstraintKind::Seeds) {
+                continue;
+            }
+            // A bare `bump` makes Anchor derive the canonical bump
+            let Some(bump) = field
+                .constraints
+                .iter()
+                .find(|c| c.kind == ConstraintKind::Bump)
+                .and_then(|c| c.value.as_ref())
+            else {
+                continue;
+            };
+            let source = bump_source(None, Some(accounts), &accounts.instruction_args, bump, 0);
+            if let Some(reason) = visitor.bumps.attacker_controlled(&source) {
+                visitor.findings.push(Finding {
+                    severity: Severity::High,
+                    check: "non-canonical-bump".into(),
+                    message: format!(
+                        "`{}` in `{}` is checked against a PDA with the bump from {}. A caller \
+                         can pick any bump that lands off the curve and pass a different \
+                         account. Use a bare `bump` or a bump stored from `ctx.bumps`.",
+                        field.name, accounts.name, reason
+                    ),
+                    file: visitor.path.clone(),
+                    line: field.line,
+                    context: None,
+                });
+            }
+        }
+    }
+
     visitor.findings
 }
 
-struct PdaVisitor<'a> {
+struct PdaVisitor<'a, 'ast> {
     path: String,
-    source: &'a str,
     findings: Vec<Finding>,
-    current_fn: Option<String>,
+    scope: Scope<'ast>,
+    bumps: &'a StoredBumps,
+    model: &'a ProgramModel<'ast>,
+    index: &'a CrateIndex<'ast>,
 }
 
-impl<'a> PdaVisitor<'a> {
+impl<'a, 'ast> PdaVisitor<'a, 'ast> {
     fn line_of_span(&self, span: proc_macro2::Span) -> usize {
         span.start().line
     }
 
-    fn get_fn_body_source(&self) -> Option<&str> {
-        let fn_name = self.current_fn.as_ref()?;
-        let start = self.source.find(&format!("fn {}", fn_name))?;
-        // Grab a reasonable window of the function body
-        let end = self.source.len().min(start + 3000);
-        Some(&self.source[start..end])
-    }
-
     fn is_find_program_address(func: &Expr) -> bool {
         match func {
             Expr::Path(p) => {
-                let segments: Vec<String> =
-                    p.path.segments.iter().map(|s| s.ident.to_string()).collect();
+                let segments: Vec<String> = p
+                    .path
+                    .segments
+                    .iter()
+                    .map(|s| s.ident.to_string())
+                    .collect();
                 let full = segments.join("::");
                 full.contains("find_program_address")
             }
@@ -53,8 +107,12 @@ impl<'a> PdaVisitor<'a> {
     fn is_create_program_address(func: &Expr) -> bool {
         match func {
             Expr::Path(p) => {
-                let segments: Vec<String> =
-                    p.path.segments.iter().map(|s| s.ident.to_string()).collect();
+                let segments: Vec<String> = p
+                    .path
+                    .segments
+                    .iter()
+                    .map(|s| s.ident.to_string())
+                    .collect();
                 let full = segments.join("::");
                 full.contains("create_program_address")
             }
@@ -62,62 +120,136 @@ impl<'a> PdaVisitor<'a> {
         }
     }
 
-    fn check_program_id_verification(&self, line: usize) -> bool {
-        if let Some(fn_src) = self.get_fn_body_source() {
-            // Look for program_id verification patterns
-            return fn_src.contains("program_id")
-                || fn_src.contains("program.key()")
-                || fn_src.contains("crate::ID")
-                || fn_src.contains("crate::id()")
-                || fn_src.contains("ID.key()");
-        }
-
-        // Fallback: check nearby lines
-        let lines: Vec<&str> = self.source.lines().collect();
-        let search_start = line.saturating_sub(10);
-        let search_end = (line + 10).min(lines.len());
-        lines[search_start..search_end]
-            .iter()
-            .any(|l| l.contains("program_id") || l.contains("program.key()"))
+    fn check_program_id_verification(&self) -> bool {
+        let Some(f) = self.scope.current_fn() else {
+            return false;
+        };
+        // The check may sit in a helper the function calls
+        let f = self.index.function(&self.path, &f.name).unwrap_or(f);
+        // Look for program_id verification patterns
+        f.mentions("program_id")
+            || f.contains_tokens("program.key()")
+            || f.contains_tokens("crate::ID")
+            || f.contains_tokens("crate::id()")
+            || f.contains_tokens("ID.key()")
     }
 
-    fn check_pda_seed_safety(&self, line: usize) {
-        let lines: Vec<&str> = self.source.lines().collect();
-        if let Some(call_line) = lines.get(line.saturating_sub(1)) {
-            // Heuristic: if seeds include a user-provided key without
-            // surrounding validation, flag it
-            let has_user_key = call_line.contains(".key()") || call_line.contains(".key.as_ref()");
-            let has_to_bytes = call_line.contains(".to_le_bytes()")
-                || call_line.contains(".to_be_bytes()")
-                || call_line.contains("as_bytes()");
+    /// `create_program_address` trusts whatever bump is in the seeds.
+    fn check_create_bump(&mut self, node: &'ast ExprCall, line: usize) {
+        let Some(func) = self.scope.current_fn() else {
+            return;
+        };
+        let Some(bump) = node
+            .args
+            .first()
+            .and_then(|seeds| find_bump(func, seeds, TRACE_DEPTH))
+        else {
+            return;
+        };
+        let accounts = self.model.accounts_for(func, &self.path);
+        let source = bump_source(
+            Some(func),
+            accounts,
+            &instruction_params(func),
+            bump,
+            TRACE_DEPTH,
+        );
+        if let Some(reason) = self.bumps.attacker_controlled(&source) {
+            self.findings.push(Finding {
+                severity: Severity::High,
+                check: "non-canonical-bump".into(),
+                message: format!(
+                    "`create_program_address` derives a PDA with the bump from {}. Any bump \
+                     that lands off the curve is accepted, so several addresses pass. Use \
+                     `find_program_address` or a bump stored from `ctx.bumps`.",
+                    reason
+                ),
+                file: self.path.clone(),
+                line,
+                context: self.scope.qualified_fn(),
+            });
+        }
+    }
 
-            // Check if there's any validation of the input in surrounding context
-            let search_start = line.saturating_sub(15);
-            let search_end = (line + 5).min(lines.len());
-            let context: String = lines[search_start..search_end].join("\n");
+    /// Report seed elements an attacker chooses: instruction arguments the
+    /// function never checks (Medium), and keys of accounts that nothing
+    /// validates (Low). Keys of signers and validated accounts are fine.
+    fn check_pda_seed_safety(&mut self, node: &'ast ExprCall, call: &str, line: usize) {
+        let Some(func) = self.scope.current_fn() else {
+            return;
+        };
+        let Some(seeds) = node
+            .args
+            .first()
+            .and_then(|seeds| seed_array(func, seeds, TRACE_DEPTH))
+        else {
+            return;
+        };
+        let params = instruction_params(func);
+        let accounts = self.model.accounts_for(func, &self.path);
 
-            let has_validation = context.contains("require!")
-                || context.contains("assert!")
-                || context.contains("constraint")
-                || context.contains("has_one");
+        let mut findings = Vec::new();
+        for elem in &seeds.elems {
+            // The bump is checked separately; `&[bump]` or `&[vault.bump]`
+            if find_bump(func, elem, TRACE_DEPTH).is_some() {
+                continue;
+            }
+            let seed = compact_tokens(elem.to_token_stream());
 
-            if has_user_key && !has_validation {
-                // Don't double-report — this is informational
+            if let Some(arg) = params.iter().find(|p| tokens_contain(elem, p)) {
+                if validates_arg(func, arg) {
+                    continue;
+                }
+                findings.push((
+                    Severity::Medium,
+                    format!(
+                        "Seed `{}` passed to `{}` comes from instruction argument `{}`, which \
+                         is never checked. The caller chooses which PDA is derived. Validate \
+                         `{}` with `require!`, or derive from an account the program trusts.",
+                        seed, call, arg, arg
+                    ),
+                ));
+                continue;
             }
 
-            if has_to_bytes && !has_validation {
-                // Numeric seeds from user input can be dangerous
+            let Some(field) = accounts.and_then(|a| a.trace_field(func, elem, TRACE_DEPTH)) else {
+                continue;
+            };
+            let trusted = matches!(
+                field.type_name().as_str(),
+                "Signer" | "Account" | "AccountLoader" | "InterfaceAccount" | "Program" | "Sysvar"
+            ) || field.is_validated();
+            if !trusted {
+                findings.push((
+                    Severity::Low,
+                    format!(
+                        "Seed `{}` passed to `{}` uses the key of `{}`, a `{}` with no \
+                         constraints, so the caller chooses which PDA is derived. Make it a \
+                         `Signer`, or tie it to stored state with `has_one` or `address`.",
+                        seed,
+                        call,
+                        field.name,
+                        field.type_name()
+                    ),
+                ));
             }
         }
+
+        for (severity, message) in findings {
+            self.findings.push(Finding {
+                severity,
+                check: "pda-user-seeds".into(),
+                message,
+                file: self.path.clone(),
+                line,
+                context: self.scope.qualified_fn(),
+            });
+        }
     }
 }
 
-impl<'a, 'ast> Visit<'ast> for PdaVisitor<'a> {
-    fn visit_item_fn(&mut self, node: &'ast ItemFn) {
-        self.current_fn = Some(node.sig.ident.to_string());
-        syn::visit::visit_item_fn(self, node);
-        self.current_fn = None;
-    }
+impl<'a, 'ast> Visit<'ast> for PdaVisitor<'a, 'ast> {
+    track_scope!();
 
     fn visit_expr_call(&mut self, node: &'ast ExprCall) {
         let is_find = Self::is_find_program_address(&node.func);
@@ -128,7 +260,7 @@ impl<'a, 'ast> Visit<'ast> for PdaVisitor<'a> {
 
             // Check 1: Verify program_id is used correctly
             // For find_program_address, the second arg should be a known program_id
-            if !self.check_program_id_verification(line) {
+            if !self.check_program_id_verification() {
                 self.findings.push(Finding {
                     severity: Severity::High,
                     check: "pda-program-id".into(),
@@ -144,27 +276,476 @@ impl<'a, 'ast> Visit<'ast> for PdaVisitor<'a> {
                     ),
                     file: self.path.clone(),
                     line,
+                    context: self.scope.qualified_fn(),
                 });
             }
 
             // Check 2: Seed safety
-            if is_create && !self.check_program_id_verification(line) {
+            if is_create && !self.check_program_id_verification() {
                 self.findings.push(Finding {
                     severity: Severity::Medium,
                     check: "pda-create-unverified".into(),
-                    message: format!(
-                        "`create_program_address` is used instead of `find_program_address`. \
-                         Prefer `find_program_address` which returns the bump, preventing \
-                         PDA collision issues."
-                    ),
+                    message: "`create_program_address` is used instead of `find_program_address`. \
+                              Prefer `find_program_address` which returns the bump, preventing \
+                              PDA collision issues."
+                        .into(),
                     file: self.path.clone(),
                     line,
+                    context: self.scope.qualified_fn(),
                 });
             }
 
-            self.check_pda_seed_safety(line);
+            if is_create {
+                self.check_create_bump(node, line);
+            }
+
+            let call = if is_find {
+                "find_program_address"
+            } else {
+                "create_program_address"
+            };
+            self.check_pda_seed_safety(node, call, line);
         }
 
         syn::visit::visit_expr_call(self, node);
     }
 }
+
+/// The seed lists of every `seeds = [...]` constraint and
+/// `find_program_address` call in `file`, for the program-wide collision
+/// analysis in [`super::seeds`].
+pub fn collect_seed_shapes(file: &File, path: &str, index: &CrateIndex) -> Vec<SeedShape> {
+    let fields = FieldTypes::from_structs(index.crate_structs(path));
+    let mut shapes = Vec::new();
+
+    for accounts in collect_accounts_structs(file) {
+        for field in &accounts.fields {
+            let Some(Expr::Array(seeds)) = field
+                .constraints
+                .iter()
+                .find(|c| c.kind == ConstraintKind::Seeds)
+                .and_then(|c| c.value.as_ref())
+            else {
+                continue;
+            };
+            shapes.push(SeedShape {
+                parts: SeedShape::from_elems(&seeds.elems, &fields),
+                kind: field.inner_type(),
+                origin: format!("`{}` in `{}`", field.name, accounts.name),
+                rendered: compact_tokens(seeds.to_token_stream()),
+                file: path.to_string(),
+                line: field.line,
+            });
+        }
+    }
+
+    for func in collect_fns(file, index.module(path)) {
+        let mut calls = SeedListFinder(Vec::new());
+        func.visit(&mut calls);
+        for (line, seeds) in calls.0 {
+            let Some(seeds) = seed_array(&func, seeds, TRACE_DEPTH) else {
+                continue;
+            };
+            shapes.push(SeedShape {
+                parts: SeedShape::from_elems(&seeds.elems, &fields),
+                kind: None,
+                origin: format!("`find_program_address` in `{}`", func.name),
+                rendered: compact_tokens(seeds.to_token_stream()),
+                file: path.to_string(),
+                line,
+            });
+        }
+    }
+
+    shapes
+}
+
+/// The array literal behind a seeds argument such as `&[b"vault", ...]` or
+/// a local bound to one.
+fn seed_array<'ast>(
+    func: &FnContext<'ast>,
+    expr: &'ast Expr,
+    depth: usize,
+) -> Option<&'ast syn::ExprArray> {
+    match expr {
+        Expr::Array(a) => Some(a),
+        Expr::Reference(r) => seed_array(func, &r.expr, depth),
+        Expr::Paren(p) => seed_array(func, &p.expr, depth),
+        Expr::Path(p) if depth > 0 => {
+            let init = func.local_init(&p.path.get_ident()?.to_string())?;
+            seed_array(func, init, depth - 1)
+        }
+        _ => None,
+    }
+}
+
+/// The seeds argument of each `find_program_address` call in a body.
+struct SeedListFinder<'ast>(Vec<(usize, &'ast Expr)>);
+
+impl<'ast> Visit<'ast> for SeedListFinder<'ast> {
+    fn visit_expr_call(&mut self, node: &'ast ExprCall) {
+        if PdaVisitor::is_find_program_address(&node.func) {
+            if let Some(seeds) = node.args.first() {
+                self.0.push((node.func.span().start().line, seeds));
+            }
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+}
+
+/// Whether `func` checks the instruction argument `arg` before using it: a
+/// `require!`/`assert!`-style macro or an `if` condition that mentions it.
+fn validates_arg(func: &FnContext, arg: &str) -> bool {
+    let mut finder = ArgCheckFinder { arg, found: false };
+    func.visit(&mut finder);
+    finder.found
+}
+
+struct ArgCheckFinder<'n> {
+    arg: &'n str,
+    found: bool,
+}
+
+impl<'n, 'ast> Visit<'ast> for ArgCheckFinder<'n> {
+    fn visit_macro(&mut self, node: &'ast syn::Macro) {
+        let checking = node.path.segments.last().is_some_and(|s| {
+            let name = s.ident.to_string();
+            name.starts_with("require") || name.starts_with("assert")
+        });
+        if checking && tokens_contain(&node.tokens, self.arg) {
+            self.found = true;
+        }
+        syn::visit::visit_macro(self, node);
+    }
+
+    fn visit_expr_if(&mut self, node: &'ast syn::ExprIf) {
+        if tokens_contain(&node.cond, self.arg) {
+            self.found = true;
+        }
+        syn::visit::visit_expr_if(self, node);
+    }
+}
+
+/// Where a bump value comes from.
+pub enum BumpSource {
+    /// `ctx.bumps`, `find_program_address`, or a bare `bump` constraint.
+    Canonical,
+    /// An instruction argument, by name.
+    Argument(String),
+    /// A stored field, e.g. `bump` in `state.bump`, with the account type
+    /// that holds it when it can be resolved.
+    Stored {
+        account: Option<String>,
+        field: String,
+    },
+    Unknown,
+}
+
+fn is_bump_like(name: &str) -> bool {
+    name.contains("bump") || name == "nonce"
+}
+
+/// The names of a function's instruction arguments: every typed parameter
+/// except the `Context<T>`.
+pub fn instruction_params(func: &FnContext) -> Vec<String> {
+    let Some(sig) = func.sig else {
+        return Vec::new();
+    };
+    sig.inputs
+        .iter()
+        .filter_map(|input| match input {
+            syn::FnArg::Typed(pt) => match (&*pt.pat, &*pt.ty) {
+                (_, syn::Type::Path(tp))
+                    if tp
+                        .path
+                        .segments
+                        .last()
+                        .is_some_and(|s| s.ident == "Context") =>
+                {
+                    None
+                }
+                (syn::Pat::Ident(pi), _) => Some(pi.ident.to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Classify the bump `expr`. `params` are the instruction arguments in
+/// scope; locals are followed through `func` up to `depth` bindings, and
+/// stored fields are typed through the fields of `accounts`.
+pub fn bump_source<'ast>(
+    func: Option<&FnContext<'ast>>,
+    accounts: Option<&AccountsStruct<'ast>>,
+    params: &[String],
+    expr: &'ast Expr,
+    depth: usize,
+) -> BumpSource {
+    if tokens_contain(expr, "bumps") || tokens_contain(expr, "find_program_address") {
+        return BumpSource::Canonical;
+    }
+    match expr {
+        Expr::Path(p) => {
+            let Some(ident) = p.path.get_ident().map(|i| i.to_string()) else {
+                return BumpSource::Unknown;
+            };
+            if params.contains(&ident) {
+                return BumpSource::Argument(ident);
+            }
+            match func.and_then(|f| f.local_init(&ident)) {
+                Some(init) if depth > 0 => bump_source(func, accounts, params, init, depth - 1),
+                _ => BumpSource::Unknown,
+            }
+        }
+        Expr::Field(f) => match &f.member {
+            syn::Member::Named(m) if is_bump_like(&m.to_string()) => BumpSource::Stored {
+                account: stored_in(func, accounts, &f.base),
+                field: m.to_string(),
+            },
+            _ => BumpSource::Unknown,
+        },
+        Expr::Array(a) if a.elems.len() == 1 => {
+            bump_source(func, accounts, params, &a.elems[0], depth)
+        }
+        Expr::Reference(r) => bump_source(func, accounts, params, &r.expr, depth),
+        Expr::Paren(p) => bump_source(func, accounts, params, &p.expr, depth),
+        Expr::Unary(u) => bump_source(func, accounts, params, &u.expr, depth),
+        Expr::Cast(c) => bump_source(func, accounts, params, &c.expr, depth),
+        Expr::Try(t) => bump_source(func, accounts, params, &t.expr, depth),
+        Expr::MethodCall(m) => bump_source(func, accounts, params, &m.receiver, depth),
+        _ => BumpSource::Unknown,
+    }
+}
+
+/// The account data type `base` refers to, e.g. `Vault` for
+/// `ctx.accounts.vault` when `accounts` declares
+/// `vault: Account<'info, Vault>`.
+fn stored_in<'ast>(
+    func: Option<&FnContext<'ast>>,
+    accounts: Option<&AccountsStruct<'ast>>,
+    base: &'ast Expr,
+) -> Option<String> {
+    let accounts = accounts?;
+    let field = match func {
+        Some(func) => accounts.trace_field(func, base, TRACE_DEPTH),
+        None => accounts
+            .fields
+            .iter()
+            .find(|f| tokens_contain(base, &f.name)),
+    }?;
+    let ty = field.inner_type()?;
+    Some(ty.rsplit("::").next().unwrap_or(&ty).to_string())
+}
+
+/// The bump element of a seed list such as `&[b"vault", &[bump]]`: the
+/// first bump-like identifier or field, looking through up to `depth`
+/// `let` bindings of the locals it mentions.
+pub fn find_bump<'ast>(
+    func: &FnContext<'ast>,
+    seeds: &'ast Expr,
+    depth: usize,
+) -> Option<&'ast Expr> {
+    let mut finder = BumpFinder {
+        found: None,
+        locals: Vec::new(),
+    };
+    finder.visit_expr(seeds);
+    if finder.found.is_some() || depth == 0 {
+        return finder.found;
+    }
+    finder.locals.iter().find_map(|local| {
+        let init = func.local_init(local)?;
+        find_bump(func, init, depth - 1)
+    })
+}
+
+struct BumpFinder<'ast> {
+    found: Option<&'ast Expr>,
+    locals: Vec<String>,
+}
+
+impl<'ast> Visit<'ast> for BumpFinder<'ast> {
+    fn visit_expr(&mut self, node: &'ast Expr) {
+        if self.found.is_some() {
+            return;
+        }
+        match node {
+            Expr::Path(p) => {
+                if let Some(ident) = p.path.get_ident().map(|i| i.to_string()) {
+                    if is_bump_like(&ident) {
+                        self.found = Some(node);
+                    } else {
+                        self.locals.push(ident);
+                    }
+                }
+                return;
+            }
+            Expr::Field(f) => {
+                if let syn::Member::Named(m) = &f.member {
+                    if is_bump_like(&m.to_string()) {
+                        self.found = Some(node);
+                        return;
+                    }
+                }
+            }
+            _ => {}
+        }
+        syn::visit::visit_expr(self, node);
+    }
+}
+
+/// Every write of a bump-like field in the crate, e.g. `state.bump = bump;`
+/// or `Vault { bump: ctx.bumps.vault, .. }`, with the account type it is
+/// stored in and where its value came from.
+pub struct StoredBumps(Vec<StoredBump>);
+
+struct StoredBump {
+    account: Option<String>,
+    field: String,
+    source: BumpSource,
+    func: String,
+}
+
+impl StoredBumps {
+    /// Collect the writes made by every function of the crate, so a bump
+    /// stored by one instruction is known where another signs with it.
+    pub fn collect<'ast>(index: &CrateIndex<'ast>, model: &ProgramModel<'ast>) -> Self {
+        let mut stored = Vec::new();
+        for f in &index.fns {
+            let func = &f.item;
+            let accounts = model.accounts_for(func, &f.file);
+            let params = instruction_params(func);
+            let mut writes = BumpWrites {
+                func,
+                accounts,
+                writes: Vec::new(),
+            };
+            func.visit(&mut writes);
+            for (account, field, value) in writes.writes {
+                stored.push(StoredBump {
+                    account,
+                    field,
+                    source: bump_source(Some(func), accounts, &params, value, TRACE_DEPTH),
+                    func: func.simple_name().to_string(),
+                });
+            }
+        }
+        StoredBumps(stored)
+    }
+
+    /// Why a bump from `source` is attacker-controlled, or `None` when it is
+    /// canonical or cannot be traced. A stored bump is trusted unless some
+    /// function writes it from an instruction argument.
+    pub fn attacker_controlled(&self, source: &BumpSource) -> Option<String> {
+        match source {
+            BumpSource::Argument(arg) => Some(format!("instruction argument `{}`", arg)),
+            BumpSource::Stored { account, field } => self.0.iter().find_map(|s| {
+                // Writes to the same field of another account type are
+                // unrelated; an unknown type on either side still matches
+                let same_account =
+                    s.account.is_none() || account.is_none() || s.account == *account;
+                match &s.source {
+                    BumpSource::Argument(arg) if s.field == *field && same_account => {
+                        let name = match s.account.as_ref().or(account.as_ref()) {
+                            Some(account) => format!("{}.{}", account, field),
+                            None => field.clone(),
+                        };
+                        Some(format!(
+                            "the stored `{}` field, which `{}` writes from instruction \
+                             argument `{}`",
+                            name, s.func, arg
+                        ))
+                    }
+                    _ => None,
+                }
+            }),
+            BumpSource::Canonical | BumpSource::Unknown => None,
+        }
+    }
+}
+
+struct BumpWrites<'f, 'ast> {
+    func: &'f FnContext<'ast>,
+    accounts: Option<&'f AccountsStruct<'ast>>,
+    /// The account type written to, when known, the field and the value.
+    writes: Vec<(Option<String>, String, &'ast Expr)>,
+}
+
+impl<'f, 'ast> Visit<'ast> for BumpWrites<'f, 'ast> {
+    fn visit_expr_assign(&mut self, node: &'ast syn::ExprAssign) {
+        if let Expr::Field(f) = &*node.left {
+            if let syn::Member::Named(m) = &f.member {
+                if is_bump_like(&m.to_string()) {
+                    let account = stored_in(Some(self.func), self.accounts, &f.base);
+                    self.writes.push((account, m.to_string(), &node.right));
+                }
+            }
+        }
+        syn::visit::visit_expr_assign(self, node);
+    }
+
+    fn visit_expr_struct(&mut self, node: &'ast syn::ExprStruct) {
+        let account = node.path.segments.last().map(|s| s.ident.to_string());
+        for field in &node.fields {
+            if let syn::Member::Named(m) = &field.member {
+                if is_bump_like(&m.to_string()) {
+                    self.writes
+                        .push((account.clone(), m.to_string(), &field.expr));
+                }
+            }
+        }
+        syn::visit::visit_expr_struct(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::check_source;
+
+    #[test]
+    fn unchecked_argument_in_seeds() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod names {
+                pub fn claim(ctx: Context<Claim>, name: String) -> Result<()> {
+                    let (pda, _) = Pubkey::find_program_address(
+                        &[name.trim_end_matches("bump").as_bytes()],
+                        ctx.program_id,
+                    );
+                    Ok(())
+                }
+            }
+            "#,
+            "pda-user-seeds",
+        );
+        assert_eq!(findings.len(), 1, "{findings:?}");
+        assert!(findings[0].message.contains("`name`"));
+    }
+
+    #[test]
+    fn trusted_seeds_and_bump() {
+        let findings = check_source(
+            r#"
+            #[program]
+            pub mod names {
+                pub fn claim(ctx: Context<Claim>, bump: u8) -> Result<()> {
+                    let pda = Pubkey::create_program_address(
+                        &[b"vault", ctx.accounts.user.key().as_ref(), &[bump]],
+                        ctx.program_id,
+                    )?;
+                    Ok(())
+                }
+            }
+
+            #[derive(Accounts)]
+            pub struct Claim<'info> {
+                pub user: Signer<'info>,
+            }
+            "#,
+            "pda-user-seeds",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+}
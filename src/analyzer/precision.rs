@@ -0,0 +1,340 @@
+use super::accounts::is_data_account;
+use super::context::{compact_tokens, tokens_contain, FnContext};
+use super::index::CrateIndex;
+use super::types::{is_float, is_integer, primitive, FieldTypes, TypeEnv};
+use super::{Finding, Severity};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{BinOp, Expr, ExprBinary, ExprCast, ExprMethodCall, ItemStruct};
+
+/// Calls that move funds out; an amount reaching one of them is paid to
+/// someone.
+const PAYOUT_CALLS: &[&str] = &["transfer", "transfer_checked", "withdraw", "sub_lamports"];
+
+/// Checks for, inside `#[program]` functions and the helpers they call:
+/// 1. Integer division whose result is then multiplied (`a / b * c`)
+/// 2. `as` casts that truncate a `u128` intermediate to a narrower integer
+/// 3. `checked_div` results used as the amount of a transfer or withdrawal
+/// 4. Floating point values, which are imprecise and nondeterministic
+///    across validators
+///
+/// and, in the structs declared in `path`, floating point fields of account
+/// and instruction data.
+pub fn check_precision(path: &str, index: &CrateIndex) -> Vec<Finding> {
+    let fields = FieldTypes::from_structs(index.crate_structs(path));
+    let mut findings = Vec::new();
+
+    for item in index
+        .structs
+        .iter()
+        .filter(|s| s.file == path)
+        .map(|s| s.item)
+    {
+        if !is_on_chain(item) {
+            continue;
+        }
+        let mut floats = FloatTypes::default();
+        for field in &item.fields {
+            floats.visit_type(&field.ty);
+        }
+        findings.extend(floats.lines.into_iter().map(|(line, ty)| Finding {
+            severity: Severity::Medium,
+            check: "precision-loss".into(),
+            message: float_message(&format!("type `{}`", ty), &item.ident.to_string()),
+            file: path.to_string(),
+            line,
+            context: Some(item.ident.to_string()),
+        }));
+    }
+
+    for func in index.program_fns(path) {
+        let mut visitor = PrecisionVisitor {
+            path,
+            func,
+            env: TypeEnv::for_fn(&fields, func),
+            quotients: Vec::new(),
+            float_lines: Vec::new(),
+            findings: Vec::new(),
+        };
+        if let Some(sig) = func.sig {
+            visitor.visit_signature(sig);
+        }
+        func.visit(&mut visitor);
+        findings.extend(visitor.findings);
+    }
+
+    findings
+}
+
+struct PrecisionVisitor<'v, 'ast> {
+    path: &'v str,
+    func: &'v FnContext<'ast>,
+    env: TypeEnv<'v>,
+    /// Locals bound from a `checked_div`.
+    quotients: Vec<String>,
+    /// Lines already reported for floats, one finding per line.
+    float_lines: Vec<usize>,
+    findings: Vec<Finding>,
+}
+
+impl<'v, 'ast> PrecisionVisitor<'v, 'ast> {
+    fn report(&mut self, line: usize, message: String) {
+        self.findings.push(Finding {
+            severity: Severity::Medium,
+            check: "precision-loss".into(),
+            message,
+            file: self.path.to_string(),
+            line,
+            context: Some(self.func.name.clone()),
+        });
+    }
+
+    fn report_float(&mut self, line: usize, what: &str) {
+        if self.float_lines.contains(&line) {
+            return;
+        }
+        self.float_lines.push(line);
+        self.report(line, float_message(what, self.func.simple_name()));
+    }
+
+    fn is_integer_expr(&self, expr: &Expr) -> bool {
+        self.env
+            .type_of(expr)
+            .is_some_and(|t| is_integer(&t) && !is_float(&t))
+    }
+
+    /// Whether `expr` is a division, looking through parentheses, `?` and
+    /// `.unwrap()`: `a / b`, `a.checked_div(b)?`.
+    fn is_division(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Paren(p) => self.is_division(&p.expr),
+            Expr::Try(t) => self.is_division(&t.expr),
+            Expr::Binary(b) => matches!(b.op, BinOp::Div(_)) && self.is_integer_expr(expr),
+            Expr::MethodCall(m) => match m.method.to_string().as_str() {
+                "checked_div" | "saturating_div" | "wrapping_div" => true,
+                "unwrap" | "expect" | "ok_or" | "ok_or_else" => self.is_division(&m.receiver),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn div_before_mul(&mut self, line: usize, node: &impl ToTokens) {
+        self.report(
+            line,
+            format!(
+                "Division before multiplication in `{}`: the quotient is truncated before it \
+                 is scaled, losing precision. Multiply first (widening to `u128` if needed), \
+                 then divide.",
+                compact_tokens(node.to_token_stream())
+            ),
+        );
+    }
+
+    /// Whether `expr` is or mentions a `checked_div` result.
+    fn mentions_quotient(&self, expr: &Expr) -> bool {
+        tokens_contain(expr, "checked_div")
+            || self.quotients.iter().any(|q| tokens_contain(expr, q))
+    }
+}
+
+impl<'v, 'ast> Visit<'ast> for PrecisionVisitor<'v, 'ast> {
+    fn visit_local(&mut self, node: &'ast syn::Local) {
+        if let (syn::Pat::Ident(pi), Some(init)) = (&node.pat, &node.init) {
+            if tokens_contain(&init.expr, "checked_div") {
+                self.quotients.push(pi.ident.to_string());
+            }
+        }
+        syn::visit::visit_local(self, node);
+    }
+
+    fn visit_expr_binary(&mut self, node: &'ast ExprBinary) {
+        if matches!(node.op, BinOp::Mul(_) | BinOp::MulAssign(_)) && self.is_division(&node.left) {
+            self.div_before_mul(node.op.span().start().line, node);
+        }
+        syn::visit::visit_expr_binary(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        let method = node.method.to_string();
+        let line = node.method.span().start().line;
+
+        if matches!(
+            method.as_str(),
+            "checked_mul" | "saturating_mul" | "wrapping_mul"
+        ) && self.is_division(&node.receiver)
+        {
+            self.div_before_mul(line, node);
+        }
+
+        if PAYOUT_CALLS.contains(&method.as_str())
+            && node.args.iter().any(|a| self.mentions_quotient(a))
+        {
+            self.report(line, payout_message(&method));
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast syn::ExprCall) {
+        let payout = match &*node.func {
+            Expr::Path(p) => p
+                .path
+                .segments
+                .last()
+                .is_some_and(|s| PAYOUT_CALLS.contains(&s.ident.to_string().as_str())),
+            _ => false,
+        };
+        if payout && node.args.iter().any(|a| self.mentions_quotient(a)) {
+            self.report(
+                node.func.span().start().line,
+                payout_message(&compact_tokens(node.func.to_token_stream())),
+            );
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_cast(&mut self, node: &'ast ExprCast) {
+        let line = node.as_token.span.start().line;
+        let target = primitive(&node.ty);
+        if let Some(target) = target.as_deref() {
+            if is_float(target) {
+                self.report_float(line, &format!("cast `as {}`", target));
+            } else if is_integer(target)
+                && !target.ends_with("128")
+                && self.env.type_of(&node.expr).as_deref() == Some("u128")
+            {
+                self.report(
+                    line,
+                    format!(
+                        "`{}` truncates a `u128` intermediate to `{}`, silently dropping the \
+                         high bits. Use `{}::try_from(...)` and return an error on overflow.",
+                        compact_tokens(node.to_token_stream()),
+                        target,
+                        target
+                    ),
+                );
+            }
+        }
+        syn::visit::visit_expr_cast(self, node);
+    }
+
+    fn visit_type(&mut self, node: &'ast syn::Type) {
+        if let Some(ty) = primitive(node).filter(|t| is_float(t)) {
+            self.report_float(node.span().start().line, &format!("type `{}`", ty));
+        }
+        syn::visit::visit_type(self, node);
+    }
+
+    fn visit_lit_float(&mut self, node: &'ast syn::LitFloat) {
+        self.report_float(node.span().start().line, "literal");
+    }
+}
+
+fn float_message(what: &str, within: &str) -> String {
+    format!(
+        "Floating point {} in `{}`. Floats round unpredictably and lose precision on large \
+         amounts. Use integer math with an explicit scale (e.g. basis points or 1e9 fixed \
+         point).",
+        what, within
+    )
+}
+
+fn payout_message(call: &str) -> String {
+    format!(
+        "`{}` pays out an amount computed with `checked_div`, which always rounds down. Make \
+         sure the rounding favours the program: round amounts paid out down and amounts owed \
+         up, and document the choice.",
+        call
+    )
+}
+
+/// Whether a struct ends up on chain: an `#[account]` or zero-copy type, or
+/// one serialized with Borsh as account or instruction data.
+fn is_on_chain(node: &ItemStruct) -> bool {
+    is_data_account(node)
+        || node.attrs.iter().any(|a| {
+            a.path().is_ident("derive")
+                && [
+                    "AnchorSerialize",
+                    "BorshSerialize",
+                    "AnchorDeserialize",
+                    "BorshDeserialize",
+                ]
+                .iter()
+                .any(|d| tokens_contain(&a.meta, d))
+        })
+}
+
+/// The `f32`/`f64` types inside a type, e.g. in `Vec<f64>`.
+#[derive(Default)]
+struct FloatTypes {
+    lines: Vec<(usize, String)>,
+}
+
+impl<'ast> Visit<'ast> for FloatTypes {
+    fn visit_type(&mut self, node: &'ast syn::Type) {
+        if let Some(ty) = primitive(node).filter(|t| is_float(t)) {
+            self.lines.push((node.span().start().line, ty));
+        }
+        syn::visit::visit_type(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::check_source;
+
+    #[test]
+    fn floats_and_rounding() {
+        let findings = check_source(
+            r#"
+            #[account]
+            pub struct Pool {
+                pub rate: f64,
+                pub history: Vec<f32>,
+            }
+
+            #[program]
+            pub mod pool {
+                pub fn claim(ctx: Context<Claim>, shares: u64) -> Result<()> {
+                    let per_share = ctx.accounts.pool.total / ctx.accounts.pool.supply * shares;
+                    let owed = shares.checked_div(3).unwrap();
+                    token::transfer(cpi, owed)?;
+                    Ok(())
+                }
+            }
+            "#,
+            "precision-loss",
+        );
+        let lines: Vec<usize> = findings.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [4, 5, 11, 13], "{findings:?}");
+    }
+
+    #[test]
+    fn integer_math_and_off_chain_floats() {
+        let findings = check_source(
+            r#"
+            #[account]
+            pub struct Pool {
+                pub rate_bps: u16,
+            }
+
+            pub struct Display {
+                pub ratio: f64,
+            }
+
+            #[program]
+            pub mod pool {
+                pub fn claim(ctx: Context<Claim>, shares: u64) -> Result<()> {
+                    let owed = shares * ctx.accounts.pool.total / ctx.accounts.pool.supply;
+                    token::transfer(cpi, owed)?;
+                    Ok(())
+                }
+            }
+            "#,
+            "precision-loss",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+}
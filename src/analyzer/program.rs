@@ -0,0 +1,237 @@
+use super::constraints::AccountsStruct;
+use super::context::{context_accounts_path, FnContext};
+use super::index::CrateIndex;
+
+/// A `#[derive(Accounts)]` struct together with where it is declared.
+pub struct DeclaredAccounts<'ast> {
+    pub accounts: AccountsStruct<'ast>,
+    pub file: String,
+    /// Index into `CrateIndex::structs`.
+    symbol: usize,
+}
+
+/// A function taking a `Context<T>`, with `T` resolved to its struct.
+pub struct BoundHandler<'ast> {
+    pub func: FnContext<'ast>,
+    pub file: String,
+    /// Index into `ProgramModel::structs`; `None` when `T` is not declared
+    /// in the scanned sources.
+    accounts: Option<usize>,
+}
+
+impl<'ast> BoundHandler<'ast> {
+    /// Whether this is an instruction: a handler in the `#[program]` module.
+    pub fn is_instruction(&self) -> bool {
+        self.func.in_program
+    }
+}
+
+/// Which accounts struct belongs to which handler, across every scanned
+/// file. `Context<T>` is resolved through the crate index, so a
+/// `#[program]` module in `lib.rs` finds structs declared under
+/// `instructions/`.
+pub struct ProgramModel<'ast> {
+    structs: Vec<DeclaredAccounts<'ast>>,
+    handlers: Vec<BoundHandler<'ast>>,
+}
+
+impl<'ast> ProgramModel<'ast> {
+    /// Build the model from the crate index.
+    pub fn build(index: &CrateIndex<'ast>) -> Self {
+        let structs: Vec<DeclaredAccounts> = index
+            .structs
+            .iter()
+            .enumerate()
+            .filter_map(|(symbol, s)| {
+                Some(DeclaredAccounts {
+                    accounts: AccountsStruct::from_item(s.item)?,
+                    file: s.file.clone(),
+                    symbol,
+                })
+            })
+            .collect();
+
+        let handlers = index
+            .fns
+            .iter()
+            .filter_map(|f| {
+                let ty = f.item.sig.and_then(context_accounts_path)?;
+                let symbol = index.resolve_struct(&f.file, ty);
+                Some(BoundHandler {
+                    func: f.item.clone(),
+                    file: f.file.clone(),
+                    accounts: symbol.and_then(|i| structs.iter().position(|s| s.symbol == i)),
+                })
+            })
+            .collect();
+
+        ProgramModel { structs, handlers }
+    }
+
+    /// The accounts struct `func`, declared in `file`, works on: the `T` of
+    /// its `Context<T>` parameter, or the struct whose `impl` block it is in.
+    pub fn accounts_for(&self, func: &FnContext, file: &str) -> Option<&AccountsStruct<'ast>> {
+        if func.sig.and_then(context_accounts_path).is_some() {
+            let handler = self
+                .handlers
+                .iter()
+                .find(|h| h.file == file && h.func.name == func.name)?;
+            return Some(&self.structs[handler.accounts?].accounts);
+        }
+        let segments: Vec<&str> = func.name.split("::").collect();
+        let mut candidates = self
+            .structs
+            .iter()
+            .filter(|s| segments.contains(&s.accounts.name.as_str()));
+        let first = candidates.next()?;
+        let same_file = std::iter::once(first)
+            .chain(candidates)
+            .find(|s| s.file == file);
+        Some(&same_file.unwrap_or(first).accounts)
+    }
+
+    /// The struct named `name` declared in `file`.
+    pub fn declared(&self, file: &str, name: &str) -> Option<&AccountsStruct<'ast>> {
+        self.structs
+            .iter()
+            .find(|s| s.file == file && s.accounts.name == name)
+            .map(|s| &s.accounts)
+    }
+
+    /// Every handler, in any file, whose `Context<T>` resolves to `accounts`.
+    pub fn handlers_of(&self, accounts: &AccountsStruct) -> Vec<&BoundHandler<'ast>> {
+        self.handlers
+            .iter()
+            .filter(|h| {
+                h.accounts
+                    .is_some_and(|i| std::ptr::eq(&self.structs[i].accounts, accounts))
+            })
+            .collect()
+    }
+
+    /// The handlers declared in `file` whose accounts struct is known.
+    pub fn handlers_in<'m>(
+        &'m self,
+        file: &'m str,
+    ) -> impl Iterator<Item = (&'m BoundHandler<'ast>, &'m AccountsStruct<'ast>)> + 'm {
+        self.handlers
+            .iter()
+            .filter(move |h| h.file == file)
+            .filter_map(|h| Some((h, &self.structs[h.accounts?].accounts)))
+    }
+
+    /// Names of the instructions that take `accounts`, e.g.
+    /// `["deposit", "withdraw"]`.
+    pub fn instructions_of(&self, accounts: &AccountsStruct) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .handlers_of(accounts)
+            .into_iter()
+            .filter(|h| h.is_instruction())
+            .map(|h| h.func.simple_name())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CrateIndex, ProgramModel};
+
+    /// Each handler in `files` with the file of the struct it is bound to.
+    fn bindings(files: &[(&str, &str)]) -> Vec<(String, Option<String>)> {
+        let parsed: Vec<(String, syn::File)> = files
+            .iter()
+            .map(|(path, source)| {
+                let syntax = syn::parse_file(source).expect("test source parses");
+                (path.to_string(), syntax)
+            })
+            .collect();
+        let files: Vec<(String, &syn::File)> = parsed.iter().map(|(p, s)| (p.clone(), s)).collect();
+        let index = CrateIndex::build(&files, 3);
+        let model = ProgramModel::build(&index);
+        model
+            .handlers
+            .iter()
+            .map(|h| {
+                let file = h.accounts.map(|i| model.structs[i].file.clone());
+                (h.func.name.clone(), file)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn handlers_link_across_files() {
+        let bindings = bindings(&[
+            (
+                "src/lib.rs",
+                r#"
+                mod instructions;
+                mod legacy;
+                use instructions::deposit::Deposit;
+
+                #[program]
+                pub mod vault {
+                    use super::*;
+
+                    pub fn deposit(ctx: Context<Deposit>) -> Result<()> {
+                        Ok(())
+                    }
+                }
+                "#,
+            ),
+            (
+                "src/legacy.rs",
+                r#"
+                #[derive(Accounts)]
+                pub struct Deposit<'info> {
+                    pub payer: Signer<'info>,
+                }
+                "#,
+            ),
+            (
+                "src/instructions/deposit.rs",
+                r#"
+                #[derive(Accounts)]
+                pub struct Deposit<'info> {
+                    pub user: Signer<'info>,
+                }
+                "#,
+            ),
+        ]);
+        let expected = Some("src/instructions/deposit.rs".to_string());
+        assert_eq!(bindings, [("vault::deposit".to_string(), expected)]);
+    }
+
+    #[test]
+    fn external_context_types_stay_unlinked() {
+        let bindings = bindings(&[
+            (
+                "src/lib.rs",
+                r#"
+                use other_program::Deposit;
+
+                #[program]
+                pub mod vault {
+                    use super::*;
+
+                    pub fn deposit(ctx: Context<Deposit>) -> Result<()> {
+                        Ok(())
+                    }
+                }
+                "#,
+            ),
+            (
+                "src/legacy.rs",
+                r#"
+                #[derive(Accounts)]
+                pub struct Deposit<'info> {
+                    pub payer: Signer<'info>,
+                }
+                "#,
+            ),
+        ]);
+        assert_eq!(bindings, [("vault::deposit".to_string(), None)]);
+    }
+}
@@ -0,0 +1,368 @@
+use super::context::{
+    collect_fns, compact_tokens, compares_in_macro, pat_idents, tokens_contain, writes_through,
+    Aliases,
+};
+use super::index::CrateIndex;
+use super::owner::DESERIALIZERS;
+use super::{Finding, Severity};
+use quote::ToTokens;
+use std::collections::HashSet;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{
+    BinOp, Expr, ExprBinary, ExprCall, ExprForLoop, ExprMethodCall, ExprPath, File, Local, Macro,
+    Pat, Token,
+};
+
+/// Methods that hand out mutable access to an account's data or lamports.
+pub const MUT_BORROWS: &[&str] = &[
+    "try_borrow_mut_data",
+    "try_borrow_mut_lamports",
+    "borrow_mut",
+    "realloc",
+    "assign",
+];
+
+/// Checks for:
+/// 1. Accounts taken from `ctx.remaining_accounts` that are deserialized,
+///    written to or passed to a CPI without any owner or key comparison
+pub fn check_remaining_accounts(file: &File, path: &str, index: &CrateIndex) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for func in collect_fns(file, index.module(path)) {
+        if !func.mentions("remaining_accounts") {
+            continue;
+        }
+
+        let aliases = func.aliases("remaining_accounts");
+        let mut sites = SiteFinder {
+            aliases: &aliases,
+            binding: Vec::new(),
+            accesses: Vec::new(),
+            uses: Vec::new(),
+            guarded: HashSet::new(),
+        };
+        func.visit(&mut sites);
+
+        let mut by_local = Vec::new();
+        for local in &aliases.locals {
+            for line in writes_through(&func, &Aliases::local(local)) {
+                sites.uses.push((line, "written to", vec![local.clone()]));
+                by_local.push(line);
+            }
+        }
+        for line in writes_through(&func, &aliases) {
+            if !by_local.contains(&line) {
+                sites
+                    .uses
+                    .push((line, "written to", vec!["remaining_accounts".into()]));
+            }
+        }
+
+        let mut uses: Vec<(usize, &str)> = sites
+            .uses
+            .iter()
+            .filter(|(_, _, subjects)| !sites.any_guarded(subjects))
+            .map(|(line, what, _)| (*line, *what))
+            .collect();
+        if uses.is_empty() {
+            continue;
+        }
+        uses.sort();
+        uses.dedup();
+        let uses: Vec<String> = uses
+            .iter()
+            .map(|(line, what)| format!("{} on line {}", what, line))
+            .collect();
+
+        for (line, access, subjects) in &sites.accesses {
+            if sites.any_guarded(subjects) {
+                continue;
+            }
+            findings.push(Finding {
+                severity: Severity::High,
+                check: "remaining-accounts".into(),
+                message: format!(
+                    "`{}` in `{}` takes accounts Anchor never validates, and they are {} \
+                     without an owner or key comparison. Check `.owner` and/or `.key()` of \
+                     each account before using it, or deserialize with `Account::try_from`.",
+                    access,
+                    func.simple_name(),
+                    uses.join(", ")
+                ),
+                file: path.to_string(),
+                line: *line,
+                context: Some(func.name.clone()),
+            });
+        }
+    }
+
+    findings
+}
+
+fn is_remaining_accounts(expr: &Expr) -> bool {
+    matches!(expr, Expr::Field(f) if matches!(&f.member, syn::Member::Named(n) if n == "remaining_accounts"))
+}
+
+/// Collects, for one function, where `remaining_accounts` is accessed (with
+/// the index or iterator used), where the accounts it yields are used in a
+/// sensitive way, and which of them are owner- or key-checked.
+///
+/// Each access, use and check is tied to its subjects: the locals it goes
+/// through (`acc`, `vault`) or, for direct accesses, the access itself
+/// (`ctx.remaining_accounts[1]`), so that a check on one account does not
+/// cover another.
+struct SiteFinder<'n> {
+    aliases: &'n Aliases,
+    /// Locals bound by the `let` or `for` whose initializer is being visited.
+    binding: Vec<String>,
+    accesses: Vec<(usize, String, Vec<String>)>,
+    uses: Vec<(usize, &'static str, Vec<String>)>,
+    guarded: HashSet<String>,
+}
+
+impl<'n> SiteFinder<'n> {
+    fn subjects(&self, node: &Expr) -> Vec<String> {
+        let mut subjects = Subjects {
+            aliases: self.aliases,
+            found: Vec::new(),
+        };
+        subjects.visit_expr(node);
+        subjects.found
+    }
+
+    fn any_guarded(&self, subjects: &[String]) -> bool {
+        subjects.iter().any(|s| self.guarded.contains(s))
+    }
+
+    /// Marks the accounts compared in `node` as checked when the comparison
+    /// involves their owner or key.
+    fn record_guard(&mut self, node: &Expr) {
+        if tokens_contain(node, ".owner") || tokens_contain(node, "key") {
+            let subjects = self.subjects(node);
+            self.guarded.extend(subjects);
+        }
+    }
+
+    fn with_binding(&mut self, pat: &Pat, visit: impl FnOnce(&mut Self)) {
+        let outer = std::mem::replace(&mut self.binding, pat_idents(pat));
+        visit(self);
+        self.binding = outer;
+    }
+}
+
+impl<'n, 'ast> Visit<'ast> for SiteFinder<'n> {
+    fn visit_local(&mut self, node: &'ast Local) {
+        self.with_binding(&node.pat, |this| syn::visit::visit_local(this, node));
+    }
+
+    fn visit_expr_for_loop(&mut self, node: &'ast ExprForLoop) {
+        self.with_binding(&node.pat, |this| this.visit_expr(&node.expr));
+        self.visit_block(&node.body);
+    }
+
+    fn visit_expr(&mut self, node: &'ast Expr) {
+        // The access together with what is done to it directly:
+        // `remaining_accounts[0]`, `remaining_accounts.iter()`, ...
+        if is_access(node) {
+            let line = node.span().start().line;
+            if !self.accesses.iter().any(|(l, _, _)| *l == line) {
+                let access = compact_tokens(node.to_token_stream());
+                let mut subjects = self.binding.clone();
+                subjects.push(access.clone());
+                self.accesses.push((line, access, subjects));
+            }
+        }
+        syn::visit::visit_expr(self, node);
+    }
+
+    fn visit_expr_call(&mut self, node: &'ast ExprCall) {
+        if let Expr::Path(p) = &*node.func {
+            let segments: Vec<String> = p
+                .path
+                .segments
+                .iter()
+                .map(|s| s.ident.to_string())
+                .collect();
+            let subjects: Vec<String> = node.args.iter().flat_map(|a| self.subjects(a)).collect();
+            let line = node.func.span().start().line;
+            match segments.as_slice() {
+                _ if subjects.is_empty() => {}
+                [.., name] if DESERIALIZERS.contains(&name.as_str()) => {
+                    self.uses.push((line, "deserialized", subjects));
+                }
+                [.., name] if name == "invoke" || name == "invoke_signed" => {
+                    self.uses.push((line, "passed to a CPI", subjects));
+                }
+                [.., ty, ctor] if ty == "CpiContext" && ctor.starts_with("new") => {
+                    self.uses.push((line, "passed to a CPI", subjects));
+                }
+                // `Account::try_from` checks the owner and discriminator
+                [.., ty, ctor] if ctor == "try_from" && ty.starts_with("Account") => {
+                    self.guarded.extend(subjects);
+                }
+                _ => {}
+            }
+        }
+        syn::visit::visit_expr_call(self, node);
+    }
+
+    fn visit_expr_method_call(&mut self, node: &'ast ExprMethodCall) {
+        let method = node.method.to_string();
+        let what = if MUT_BORROWS.contains(&method.as_str()) {
+            Some("written to")
+        } else if DESERIALIZERS.contains(&method.as_str()) {
+            Some("deserialized")
+        } else {
+            None
+        };
+        if let Some(what) = what {
+            let subjects = self.subjects(&node.receiver);
+            if !subjects.is_empty() {
+                let line = node.method.span().start().line;
+                self.uses.push((line, what, subjects));
+            }
+        }
+        if matches!(method.as_str(), "eq" | "ne") {
+            self.record_guard(&Expr::MethodCall(node.clone()));
+        }
+        syn::visit::visit_expr_method_call(self, node);
+    }
+
+    fn visit_expr_binary(&mut self, node: &'ast ExprBinary) {
+        if matches!(node.op, BinOp::Eq(_) | BinOp::Ne(_)) {
+            self.record_guard(&Expr::Binary(node.clone()));
+        }
+        syn::visit::visit_expr_binary(self, node);
+    }
+
+    fn visit_macro(&mut self, node: &'ast Macro) {
+        // require_keys_eq!, require!, assert_eq! and friends; `msg!` and
+        // other macros that only mention an account check nothing
+        if compares_in_macro(node) {
+            if let Ok(args) = node.parse_body_with(Punctuated::<Expr, Token![,]>::parse_terminated)
+            {
+                for arg in &args {
+                    self.record_guard(arg);
+                }
+            }
+        }
+        syn::visit::visit_macro(self, node);
+    }
+}
+
+fn is_access(expr: &Expr) -> bool {
+    match expr {
+        Expr::Index(i) => is_remaining_accounts(&i.expr),
+        Expr::MethodCall(m) => is_remaining_accounts(&m.receiver),
+        _ => is_remaining_accounts(expr),
+    }
+}
+
+/// The accounts an expression goes through: locals bound from
+/// `remaining_accounts`, and direct accesses to it.
+struct Subjects<'n> {
+    aliases: &'n Aliases,
+    found: Vec<String>,
+}
+
+impl<'n, 'ast> Visit<'ast> for Subjects<'n> {
+    fn visit_expr(&mut self, node: &'ast Expr) {
+        if is_access(node) {
+            self.found.push(compact_tokens(node.to_token_stream()));
+            return;
+        }
+        syn::visit::visit_expr(self, node);
+    }
+
+    fn visit_expr_path(&mut self, node: &'ast ExprPath) {
+        if let Some(ident) = node.path.get_ident() {
+            let ident = ident.to_string();
+            if self.aliases.locals.contains(&ident) {
+                self.found.push(ident);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::check_source;
+
+    #[test]
+    fn unchecked_write() {
+        let findings = check_source(
+            r#"
+            pub fn sweep(ctx: Context<Sweep>) -> Result<()> {
+                let target = &ctx.remaining_accounts[0];
+                **target.try_borrow_mut_lamports()? += 1;
+                Ok(())
+            }
+            "#,
+            "remaining-accounts",
+        );
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 3);
+    }
+
+    #[test]
+    fn logging_the_key_is_not_a_check() {
+        let findings = check_source(
+            r#"
+            pub fn sweep(ctx: Context<Sweep>) -> Result<()> {
+                let target = &ctx.remaining_accounts[0];
+                msg!("target {}", target.key);
+                **target.try_borrow_mut_lamports()? += 1;
+                Ok(())
+            }
+            "#,
+            "remaining-accounts",
+        );
+        assert_eq!(findings.len(), 1);
+    }
+
+    #[test]
+    fn check_on_one_account_does_not_cover_another() {
+        let findings = check_source(
+            r#"
+            pub fn sweep(ctx: Context<Sweep>) -> Result<()> {
+                let a = &ctx.remaining_accounts[0];
+                let b = &ctx.remaining_accounts[1];
+                require_keys_eq!(a.key(), ctx.accounts.vault.key());
+                **b.try_borrow_mut_lamports()? += 1;
+                **a.try_borrow_mut_lamports()? -= 1;
+                Ok(())
+            }
+            "#,
+            "remaining-accounts",
+        );
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].line, 4);
+        assert!(findings[0].message.contains("line 6"));
+        assert!(!findings[0].message.contains("line 7"));
+    }
+
+    #[test]
+    fn checked_accounts() {
+        let findings = check_source(
+            r#"
+            pub fn sweep(ctx: Context<Sweep>) -> Result<()> {
+                for acc in ctx.remaining_accounts.iter() {
+                    require!(acc.owner == ctx.program_id, ErrorCode::WrongOwner);
+                    **acc.try_borrow_mut_lamports()? += 1;
+                }
+                let first = &ctx.remaining_accounts[0];
+                let vault = Account::<Vault>::try_from(first)?;
+                if ctx.remaining_accounts[1].key() != vault.authority {
+                    return err!(ErrorCode::WrongAuthority);
+                }
+                **ctx.remaining_accounts[1].try_borrow_mut_lamports()? += 1;
+                Ok(())
+            }
+            "#,
+            "remaining-accounts",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+}
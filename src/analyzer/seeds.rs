@@ -0,0 +1,411 @@
+use super::context::{compact_tokens, tokens_contain};
+use super::index::CrateIndex;
+use super::types::{primitive, FieldTypes};
+use super::{Finding, Severity};
+use quote::ToTokens;
+use std::collections::{HashMap, HashSet, VecDeque};
+use syn::{Expr, Lit};
+
+/// The longest a single seed may be.
+const MAX_SEED_LEN: usize = 32;
+
+/// Possible widths of an integer seed whose type is not known.
+const INT_WIDTHS: &[usize] = &[1, 2, 4, 8, 16];
+
+/// One element of a seed list, reduced to what matters for collisions.
+#[derive(Clone, PartialEq, Eq)]
+pub enum SeedPart {
+    /// Known bytes, e.g. `b"vault"`.
+    Literal(Vec<u8>),
+    /// A constant whose bytes are resolved program-wide, e.g. `VAULT_SEED`.
+    Const(String),
+    /// A 32-byte public key. Not chosen freely by an attacker.
+    Pubkey,
+    /// An integer's bytes; the width when the type is known.
+    Int(Option<usize>),
+    /// Bytes of attacker-chosen length and content, e.g. `name.as_bytes()`.
+    Bytes,
+}
+
+/// The normalised seed list of one PDA derivation.
+pub struct SeedShape {
+    pub parts: Vec<SeedPart>,
+    /// The account type the seeds derive, when known.
+    pub kind: Option<String>,
+    /// What declares the seeds, e.g. `` `vault` in `Deposit` ``.
+    pub origin: String,
+    /// The seed list as written.
+    pub rendered: String,
+    pub file: String,
+    pub line: usize,
+}
+
+impl SeedShape {
+    /// Model the elements of a seed list. Bump elements are dropped: every
+    /// derivation appends one.
+    pub fn from_elems<'e>(
+        elems: impl IntoIterator<Item = &'e Expr>,
+        fields: &FieldTypes,
+    ) -> Vec<SeedPart> {
+        elems
+            .into_iter()
+            .filter_map(|e| seed_part(e, fields))
+            .collect()
+    }
+}
+
+fn is_const_name(name: &str) -> bool {
+    name.chars().any(|c| c.is_ascii_uppercase()) && !name.chars().any(|c| c.is_ascii_lowercase())
+}
+
+fn seed_part(expr: &Expr, fields: &FieldTypes) -> Option<SeedPart> {
+    match expr {
+        Expr::Lit(l) => match &l.lit {
+            Lit::ByteStr(b) => Some(SeedPart::Literal(b.value())),
+            Lit::Str(s) => Some(SeedPart::Literal(s.value().into_bytes())),
+            _ => Some(SeedPart::Int(None)),
+        },
+        Expr::Reference(r) => seed_part(&r.expr, fields),
+        Expr::Paren(p) => seed_part(&p.expr, fields),
+        // `&[bump]` is the bump; `&[kind]` is a one-byte integer
+        Expr::Array(a) if a.elems.len() == 1 => {
+            let name = compact_tokens(a.elems[0].to_token_stream());
+            (!name.contains("bump")).then_some(SeedPart::Int(Some(1)))
+        }
+        Expr::Path(p) => {
+            let name = p.path.segments.last()?.ident.to_string();
+            if name.contains("bump") {
+                None
+            } else if is_const_name(&name) {
+                Some(SeedPart::Const(name))
+            } else {
+                Some(SeedPart::Bytes)
+            }
+        }
+        Expr::MethodCall(m) => match m.method.to_string().as_str() {
+            "to_le_bytes" | "to_be_bytes" | "to_ne_bytes" => {
+                Some(SeedPart::Int(int_width(&m.receiver, fields)))
+            }
+            "key" => Some(SeedPart::Pubkey),
+            // `"vault".as_bytes()` is a literal, `name.as_bytes()` a string
+            "as_bytes" => match seed_part(&m.receiver, fields)? {
+                part @ (SeedPart::Literal(_) | SeedPart::Const(_)) => Some(part),
+                _ => Some(SeedPart::Bytes),
+            },
+            // `authority.as_ref()`, `state.mint.as_ref()`: a stored or local
+            // `Pubkey` is by far the most common byte-slice seed
+            "as_ref" | "as_slice" | "to_vec" | "borrow" => match &*m.receiver {
+                Expr::Field(_) => seed_part(&m.receiver, fields),
+                Expr::Path(p)
+                    if p.path
+                        .get_ident()
+                        .is_some_and(|i| !is_const_name(&i.to_string())) =>
+                {
+                    Some(SeedPart::Pubkey)
+                }
+                _ => seed_part(&m.receiver, fields),
+            },
+            _ if tokens_contain(&m.receiver, "key") => Some(SeedPart::Pubkey),
+            _ => Some(SeedPart::Bytes),
+        },
+        // `ctx.accounts.user.key`, `state.authority`
+        Expr::Field(f) => match &f.member {
+            syn::Member::Named(n) if n.to_string().contains("bump") => None,
+            syn::Member::Named(n) => match fields.get(&n.to_string()) {
+                Some(ty) => Some(SeedPart::Int(int_bytes(&ty))),
+                None => Some(SeedPart::Pubkey),
+            },
+            syn::Member::Unnamed(_) => Some(SeedPart::Bytes),
+        },
+        _ => Some(SeedPart::Bytes),
+    }
+}
+
+fn int_width(expr: &Expr, fields: &FieldTypes) -> Option<usize> {
+    let ty = match expr {
+        Expr::Cast(c) => primitive(&c.ty),
+        Expr::Paren(p) => return int_width(&p.expr, fields),
+        Expr::Field(f) => match &f.member {
+            syn::Member::Named(n) => fields.get(&n.to_string()),
+            syn::Member::Unnamed(_) => None,
+        },
+        Expr::Lit(l) => match &l.lit {
+            Lit::Int(i) if !i.suffix().is_empty() => Some(i.suffix().to_string()),
+            _ => None,
+        },
+        _ => None,
+    }?;
+    int_bytes(&ty)
+}
+
+/// The width in bytes of a sized integer type, e.g. 8 for `u64`.
+fn int_bytes(ty: &str) -> Option<usize> {
+    let bits: usize = ty.trim_start_matches(['u', 'i']).parse().ok()?;
+    Some(bits / 8)
+}
+
+/// Byte-string constants declared anywhere in the scanned crates, e.g.
+/// `pub const VAULT_SEED: &[u8] = b"vault";`.
+pub fn collect_seed_consts(index: &CrateIndex) -> Vec<(String, Vec<u8>)> {
+    index
+        .consts
+        .iter()
+        .filter_map(|c| {
+            let mut expr = &*c.item.expr;
+            // `"vault".as_bytes()`
+            if let Expr::MethodCall(m) = expr {
+                expr = &m.receiver;
+            }
+            match expr {
+                Expr::Lit(l) => match &l.lit {
+                    Lit::ByteStr(b) => Some((c.name.clone(), b.value())),
+                    Lit::Str(s) => Some((c.name.clone(), s.value().into_bytes())),
+                    _ => None,
+                },
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// A run of bytes in the concatenated seeds: either known bytes, or a
+/// wildcard of one of several lengths.
+struct Segment {
+    bytes: Option<Vec<u8>>,
+    /// Whether the bytes come from a public key rather than the caller.
+    pubkey: bool,
+    lengths: Vec<usize>,
+}
+
+impl Segment {
+    fn max_len(&self) -> usize {
+        self.lengths.iter().copied().max().unwrap_or(0)
+    }
+}
+
+/// A single byte of a seed string as seen by the overlap search.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Byte {
+    Known(u8),
+    Pubkey,
+    Any,
+}
+
+fn compatible(a: Byte, b: Byte) -> bool {
+    match (a, b) {
+        (Byte::Any, _) | (_, Byte::Any) => true,
+        (Byte::Pubkey, Byte::Pubkey) => true,
+        (Byte::Known(x), Byte::Known(y)) => x == y,
+        _ => false,
+    }
+}
+
+fn segments(parts: &[SeedPart], consts: &HashMap<String, Vec<u8>>) -> Vec<Segment> {
+    parts
+        .iter()
+        .map(|part| match part {
+            SeedPart::Literal(bytes) => Segment {
+                lengths: vec![bytes.len()],
+                bytes: Some(bytes.clone()),
+                pubkey: false,
+            },
+            SeedPart::Const(name) => {
+                // An unresolved constant only matches itself
+                let bytes = consts
+                    .get(name)
+                    .cloned()
+                    .unwrap_or_else(|| format!("\0{}\0", name).into_bytes());
+                Segment {
+                    lengths: vec![bytes.len()],
+                    bytes: Some(bytes),
+                    pubkey: false,
+                }
+            }
+            SeedPart::Pubkey => Segment {
+                bytes: None,
+                pubkey: true,
+                lengths: vec![32],
+            },
+            SeedPart::Int(Some(w)) => Segment {
+                bytes: None,
+                pubkey: false,
+                lengths: vec![*w],
+            },
+            SeedPart::Int(None) => Segment {
+                bytes: None,
+                pubkey: false,
+                lengths: INT_WIDTHS.to_vec(),
+            },
+            SeedPart::Bytes => Segment {
+                bytes: None,
+                pubkey: false,
+                lengths: (0..=MAX_SEED_LEN).collect(),
+            },
+        })
+        .collect()
+}
+
+/// Position in a segment list: segment index and bytes consumed in it.
+type Pos = (usize, usize);
+
+fn byte_at(segs: &[Segment], (i, k): Pos) -> Option<Byte> {
+    let seg = segs.get(i)?;
+    if k >= seg.max_len() {
+        return None;
+    }
+    Some(match &seg.bytes {
+        Some(bytes) => Byte::Known(bytes[k]),
+        None if seg.pubkey => Byte::Pubkey,
+        None => Byte::Any,
+    })
+}
+
+/// Positions reachable from `pos` by ending segments whose current length
+/// is allowed.
+fn closure(segs: &[Segment], pos: Pos) -> Vec<Pos> {
+    let mut out = vec![pos];
+    let mut cur = pos;
+    while let Some(seg) = segs.get(cur.0) {
+        if !seg.lengths.contains(&cur.1) {
+            break;
+        }
+        cur = (cur.0 + 1, 0);
+        out.push(cur);
+    }
+    out
+}
+
+/// Whether some byte string can be produced by both segment lists.
+fn overlaps(a: &[Segment], b: &[Segment]) -> bool {
+    let mut seen: HashSet<(Pos, Pos)> = HashSet::new();
+    let mut queue: VecDeque<(Pos, Pos)> = VecDeque::new();
+    queue.push_back(((0, 0), (0, 0)));
+
+    while let Some((pa, pb)) = queue.pop_front() {
+        for ca in closure(a, pa) {
+            for cb in closure(b, pb) {
+                if !seen.insert((ca, cb)) {
+                    continue;
+                }
+                if ca == (a.len(), 0) && cb == (b.len(), 0) {
+                    return true;
+                }
+                if let (Some(x), Some(y)) = (byte_at(a, ca), byte_at(b, cb)) {
+                    if compatible(x, y) {
+                        queue.push_back(((ca.0, ca.1 + 1), (cb.0, cb.1 + 1)));
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Report pairs of distinct PDA seed lists, anywhere in the scanned crate,
+/// that can hash the same bytes and so derive the same address. Seed lists
+/// of the same shape for the same account type are one PDA kind used in
+/// several places and are not compared.
+pub fn check_seed_collisions(shapes: &[SeedShape], consts: &[(String, Vec<u8>)]) -> Vec<Finding> {
+    let consts: HashMap<String, Vec<u8>> = consts.iter().cloned().collect();
+
+    // One representative per PDA kind. A `find_program_address` call has
+    // no account type, so it is taken to derive the kind whose seeds it
+    // matches.
+    let mut distinct: Vec<&SeedShape> = Vec::new();
+    for shape in shapes {
+        let same_kind = |other: &&SeedShape| {
+            other.parts == shape.parts
+                && (other.kind == shape.kind || other.kind.is_none() || shape.kind.is_none())
+        };
+        if !distinct.iter().any(same_kind) {
+            distinct.push(shape);
+        }
+    }
+
+    let segs: Vec<Vec<Segment>> = distinct
+        .iter()
+        .map(|s| segments(&s.parts, &consts))
+        .collect();
+
+    let mut findings = Vec::new();
+    for i in 0..distinct.len() {
+        for j in i + 1..distinct.len() {
+            let (a, b) = (distinct[i], distinct[j]);
+            if a.kind.is_some() && a.kind == b.kind {
+                continue;
+            }
+            if !overlaps(&segs[i], &segs[j]) {
+                continue;
+            }
+            findings.push(Finding {
+                severity: Severity::Medium,
+                check: "pda-seed-collision".into(),
+                message: format!(
+                    "Seeds `{}` ({}) can produce the same bytes as `{}` ({}, {}:{}). Seeds \
+                     are hashed as one concatenated string, so a caller can pick values that \
+                     make both derive the same PDA. Give each PDA kind a distinct literal \
+                     prefix and put variable-length seeds last or behind a fixed-length one.",
+                    b.rendered, b.origin, a.rendered, a.origin, a.file, a.line
+                ),
+                file: b.file.clone(),
+                line: b.line,
+                context: None,
+            });
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::analyzer::check_source;
+
+    #[test]
+    fn colliding_seeds() {
+        let findings = check_source(
+            r#"
+            #[derive(Accounts)]
+            #[instruction(name: String)]
+            pub struct Register<'info> {
+                #[account(init, payer = user, space = 64, seeds = [b"profile", name.as_bytes(), user.key().as_ref()], bump)]
+                pub profile: Account<'info, Profile>,
+                #[account(init, payer = user, space = 64, seeds = [b"profile_config", user.key().as_ref()], bump)]
+                pub config: Account<'info, Config>,
+                #[account(mut)]
+                pub user: Signer<'info>,
+            }
+            "#,
+            "pda-seed-collision",
+        );
+        assert_eq!(findings.len(), 1, "{findings:?}");
+        assert!(findings[0].message.contains("`config` in `Register`"));
+    }
+
+    #[test]
+    fn distinct_seed_prefixes() {
+        let findings = check_source(
+            r#"
+            #[derive(Accounts)]
+            #[instruction(name: String)]
+            pub struct Register<'info> {
+                #[account(init, payer = user, space = 64, seeds = [b"profile", user.key().as_ref(), name.as_bytes()], bump)]
+                pub profile: Account<'info, Profile>,
+                #[account(init, payer = user, space = 64, seeds = [b"config", user.key().as_ref()], bump)]
+                pub config: Account<'info, Config>,
+                #[account(mut)]
+                pub user: Signer<'info>,
+            }
+
+            #[derive(Accounts)]
+            #[instruction(name: String)]
+            pub struct Rename<'info> {
+                #[account(mut, seeds = [b"profile", user.key().as_ref(), name.as_bytes()], bump)]
+                pub profile: Account<'info, Profile>,
+                pub user: Signer<'info>,
+            }
+            "#,
+            "pda-seed-collision",
+        );
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+}
@@ -0,0 +1,205 @@
+use super::context::FnContext;
+use std::collections::HashMap;
+use syn::visit::Visit;
+use syn::{BinOp, Expr, ItemStruct, Lit, Type, UnOp};
+
+const INTEGERS: &[&str] = &[
+    "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+];
+
+/// Fields of well-known SPL account types, used when the scanned crate does
+/// not declare a field with that name itself.
+const KNOWN_FIELDS: &[(&str, &str)] = &[("amount", "u64"), ("supply", "u64"), ("decimals", "u8")];
+
+/// The type given to unsuffixed integer literals.
+pub const INT_LITERAL: &str = "{integer}";
+
+pub fn is_integer(ty: &str) -> bool {
+    INTEGERS.contains(&ty) || ty == INT_LITERAL
+}
+
+pub fn is_float(ty: &str) -> bool {
+    ty == "f32" || ty == "f64"
+}
+
+/// The primitive type named by `ty`, e.g. `u64` for `u64`. Anything that is
+/// not a bare primitive yields `None`.
+pub fn primitive(ty: &Type) -> Option<String> {
+    let Type::Path(tp) = ty else {
+        return None;
+    };
+    let ident = tp.path.get_ident()?.to_string();
+    (INTEGERS.contains(&ident.as_str()) || is_float(&ident)).then_some(ident)
+}
+
+/// Primitive-typed struct fields, by field name. When two structs declare
+/// the same field name with different types the first one wins; the
+/// inference is a best effort.
+#[derive(Default)]
+pub struct FieldTypes(HashMap<String, String>);
+
+impl FieldTypes {
+    /// The fields of `structs`, in order of precedence.
+    pub fn from_structs<'ast>(structs: impl IntoIterator<Item = &'ast ItemStruct>) -> Self {
+        let mut fields = FieldTypes::default();
+        for item in structs {
+            fields.visit_item_struct(item);
+        }
+        for (name, ty) in KNOWN_FIELDS {
+            fields
+                .0
+                .entry(name.to_string())
+                .or_insert_with(|| ty.to_string());
+        }
+        fields
+    }
+
+    /// The declared primitive type of fields named `name`.
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.0.get(name).cloned()
+    }
+}
+
+impl<'ast> Visit<'ast> for FieldTypes {
+    fn visit_field(&mut self, node: &'ast syn::Field) {
+        if let (Some(ident), Some(ty)) = (&node.ident, primitive(&node.ty)) {
+            self.0.entry(ident.to_string()).or_insert(ty);
+        }
+    }
+}
+
+/// Types visible inside one function: primitive parameters, annotated
+/// locals and locals bound from an expression whose type is known, plus the
+/// file's struct fields.
+pub struct TypeEnv<'f> {
+    fields: &'f FieldTypes,
+    locals: HashMap<String, String>,
+}
+
+impl<'f> TypeEnv<'f> {
+    pub fn for_fn(fields: &'f FieldTypes, func: &FnContext) -> Self {
+        let mut env = TypeEnv {
+            fields,
+            locals: HashMap::new(),
+        };
+        if let Some(sig) = func.sig {
+            for input in &sig.inputs {
+                if let syn::FnArg::Typed(pt) = input {
+                    if let (syn::Pat::Ident(pi), Some(ty)) = (&*pt.pat, primitive(&pt.ty)) {
+                        env.locals.insert(pi.ident.to_string(), ty);
+                    }
+                }
+            }
+        }
+        func.visit(&mut env);
+        env
+    }
+
+    /// The primitive type of `expr`, when it can be seen from the AST.
+    pub fn type_of(&self, expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Lit(lit) => match &lit.lit {
+                Lit::Int(i) if i.suffix().is_empty() => Some(INT_LITERAL.into()),
+                Lit::Int(i) => Some(i.suffix().into()),
+                Lit::Float(f) if f.suffix().is_empty() => Some("f64".into()),
+                Lit::Float(f) => Some(f.suffix().into()),
+                _ => None,
+            },
+            Expr::Path(p) => self.locals.get(&p.path.get_ident()?.to_string()).cloned(),
+            Expr::Field(f) => match &f.member {
+                syn::Member::Named(ident) => self.fields.get(&ident.to_string()),
+                syn::Member::Unnamed(_) => None,
+            },
+            Expr::Cast(c) => primitive(&c.ty),
+            Expr::Paren(p) => self.type_of(&p.expr),
+            Expr::Group(g) => self.type_of(&g.expr),
+            Expr::Reference(r) => self.type_of(&r.expr),
+            Expr::Unary(u) if matches!(u.op, UnOp::Deref(_) | UnOp::Neg(_)) => {
+                self.type_of(&u.expr)
+            }
+            Expr::Binary(b) if is_arithmetic(&b.op) => {
+                let (l, r) = (self.type_of(&b.left), self.type_of(&b.right));
+                match (l, r) {
+                    (Some(l), _) if l != INT_LITERAL => Some(l),
+                    (_, Some(r)) if r != INT_LITERAL => Some(r),
+                    (l, r) => l.or(r),
+                }
+            }
+            Expr::MethodCall(m) => match m.method.to_string().as_str() {
+                "len" => Some("usize".into()),
+                "lamports" => Some("u64".into()),
+                "pow" | "min" | "max" | "abs" => self.type_of(&m.receiver),
+                name if name.starts_with("saturating_") || name.starts_with("wrapping_") => {
+                    self.type_of(&m.receiver)
+                }
+                _ => None,
+            },
+            // `u64::from(x)`
+            Expr::Call(c) => match &*c.func {
+                Expr::Path(p) if p.path.segments.len() == 2 => {
+                    let ty = p.path.segments[0].ident.to_string();
+                    (p.path.segments[1].ident == "from" && INTEGERS.contains(&ty.as_str()))
+                        .then_some(ty)
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl<'f, 'ast> Visit<'ast> for TypeEnv<'f> {
+    fn visit_local(&mut self, node: &'ast syn::Local) {
+        match &node.pat {
+            syn::Pat::Type(pt) => {
+                if let (syn::Pat::Ident(pi), Some(ty)) = (&*pt.pat, primitive(&pt.ty)) {
+                    self.locals.insert(pi.ident.to_string(), ty);
+                }
+            }
+            syn::Pat::Ident(pi) => {
+                if let Some(ty) = node.init.as_ref().and_then(|i| self.type_of(&i.expr)) {
+                    if ty != INT_LITERAL {
+                        self.locals.insert(pi.ident.to_string(), ty);
+                    }
+                }
+            }
+            _ => {}
+        }
+        syn::visit::visit_local(self, node);
+    }
+}
+
+pub fn is_arithmetic(op: &BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::Add(_)
+            | BinOp::Sub(_)
+            | BinOp::Mul(_)
+            | BinOp::Div(_)
+            | BinOp::Rem(_)
+            | BinOp::AddAssign(_)
+            | BinOp::SubAssign(_)
+            | BinOp::MulAssign(_)
+            | BinOp::DivAssign(_)
+            | BinOp::RemAssign(_)
+    )
+}
+
+/// Literals, `SCREAMING_CASE` constants and `size_of` calls: operands whose
+/// arithmetic is evaluated (and overflow-checked) at compile time.
+pub fn is_constant(expr: &Expr) -> bool {
+    match expr {
+        Expr::Lit(_) => true,
+        Expr::Path(p) => p.path.segments.last().is_some_and(|s| {
+            let name = s.ident.to_string();
+            name.chars().any(|c| c.is_ascii_uppercase())
+                && !name.chars().any(|c| c.is_ascii_lowercase())
+        }),
+        Expr::Paren(p) => is_constant(&p.expr),
+        Expr::Binary(b) => is_constant(&b.left) && is_constant(&b.right),
+        Expr::Call(c) => {
+            matches!(&*c.func, Expr::Path(p) if p.path.segments.iter().any(|s| s.ident == "size_of"))
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,187 @@
+use super::discover_rust_files;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// An on-chain program crate and the sources to analyze for it.
+pub struct Program {
+    /// The library name, as `Anchor.toml` `[programs.*]` lists it, e.g.
+    /// `my_vault` for a package named `my-vault`.
+    pub name: String,
+    pub root: PathBuf,
+    /// The directory discovery started from, which holds the workspace
+    /// manifest when there is one.
+    pub workspace: PathBuf,
+    pub files: Vec<PathBuf>,
+}
+
+/// The program crates under `root`, each with the `.rs` files of its `src`
+/// directory. Crates are taken from the workspace `Cargo.toml` members and
+/// `Anchor.toml`, or from every manifest below `root` when it has neither,
+/// and count as programs when they build a `cdylib` or depend on
+/// `anchor-lang`.
+///
+/// With `all_files`, or when no program crate is found, every `.rs` file
+/// under `root` is analyzed as a single group instead.
+pub fn discover_programs(root: &Path, all_files: bool) -> Vec<Program> {
+    let mut programs: Vec<Program> = if all_files {
+        Vec::new()
+    } else {
+        crate_dirs(root)
+            .into_iter()
+            .filter_map(|dir| {
+                let manifest = read_toml(&dir.join("Cargo.toml"))?;
+                let name = program_name(&manifest)?;
+                let files = discover_rust_files(&dir.join("src"));
+                Some(Program {
+                    name,
+                    root: dir,
+                    workspace: root.to_path_buf(),
+                    files,
+                })
+            })
+            .collect()
+    };
+
+    if programs.is_empty() {
+        programs.push(Program {
+            name: root
+                .canonicalize()
+                .ok()
+                .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+                .unwrap_or_else(|| root.to_string_lossy().to_string()),
+            root: root.to_path_buf(),
+            workspace: root.to_path_buf(),
+            files: discover_rust_files(root),
+        });
+    }
+    programs.sort_by(|a, b| a.name.cmp(&b.name));
+    programs
+}
+
+fn read_toml(path: &Path) -> Option<toml::Table> {
+    std::fs::read_to_string(path).ok()?.parse().ok()
+}
+
+fn strings(value: Option<&toml::Value>) -> Vec<String> {
+    value
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Directories that may hold a program crate, deduplicated.
+fn crate_dirs(root: &Path) -> Vec<PathBuf> {
+    let cargo = read_toml(&root.join("Cargo.toml"));
+    let anchor = read_toml(&root.join("Anchor.toml"));
+
+    let mut dirs = Vec::new();
+    let mut excluded = Vec::new();
+    if let Some(workspace) = cargo.as_ref().and_then(|c| c.get("workspace")) {
+        for member in strings(workspace.get("members")) {
+            dirs.extend(expand(root, &member));
+        }
+        excluded.extend(
+            strings(workspace.get("exclude"))
+                .iter()
+                .map(|e| root.join(e)),
+        );
+    }
+    if cargo.as_ref().is_some_and(|c| c.contains_key("package")) {
+        dirs.push(root.to_path_buf());
+    }
+    if let Some(anchor) = &anchor {
+        if let Some(workspace) = anchor.get("workspace") {
+            for member in strings(workspace.get("members")) {
+                dirs.extend(expand(root, &member));
+            }
+            excluded.extend(
+                strings(workspace.get("exclude"))
+                    .iter()
+                    .map(|e| root.join(e)),
+            );
+        }
+        // `[programs.localnet] my_vault = "..."` lives in `programs/my_vault`
+        // or `programs/my-vault` by convention
+        let clusters = anchor.get("programs").and_then(|p| p.as_table());
+        for programs in clusters.into_iter().flat_map(|c| c.values()) {
+            for name in programs.as_table().into_iter().flat_map(|t| t.keys()) {
+                dirs.push(root.join("programs").join(name));
+                dirs.push(root.join("programs").join(name.replace('_', "-")));
+            }
+        }
+    }
+    if cargo.is_none() && anchor.is_none() {
+        dirs.extend(
+            WalkDir::new(root)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| {
+                    e.file_name() == "Cargo.toml"
+                        && !e.path().to_string_lossy().contains("/target/")
+                })
+                .filter_map(|e| e.path().parent().map(Path::to_path_buf)),
+        );
+    }
+
+    let mut seen = Vec::new();
+    dirs.retain(|d| {
+        let keep = d.join("Cargo.toml").is_file()
+            && !excluded.iter().any(|e| d.starts_with(e))
+            && !seen.contains(d);
+        seen.push(d.clone());
+        keep
+    });
+    dirs
+}
+
+/// The directories a workspace member pattern names. Only `*` wildcards
+/// are supported, e.g. `programs/*`.
+fn expand(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+    for part in pattern.split('/').filter(|p| !p.is_empty() && *p != ".") {
+        dirs = match part.split_once('*') {
+            None => dirs.into_iter().map(|d| d.join(part)).collect(),
+            Some((prefix, suffix)) => {
+                let mut matched: Vec<PathBuf> = dirs
+                    .iter()
+                    .filter_map(|d| std::fs::read_dir(d).ok())
+                    .flatten()
+                    .filter_map(|e| e.ok())
+                    .filter(|e| e.path().is_dir())
+                    .filter(|e| {
+                        let name = e.file_name().to_string_lossy().to_string();
+                        name.len() >= prefix.len() + suffix.len()
+                            && name.starts_with(prefix)
+                            && name.ends_with(suffix)
+                    })
+                    .map(|e| e.path())
+                    .collect();
+                matched.sort();
+                matched
+            }
+        };
+    }
+    dirs
+}
+
+/// The library name of a crate that builds an on-chain program: one with a
+/// `cdylib` target or an `anchor-lang` dependency.
+fn program_name(manifest: &toml::Table) -> Option<String> {
+    let lib = manifest.get("lib");
+    let cdylib = strings(lib.and_then(|l| l.get("crate-type")))
+        .iter()
+        .any(|t| t == "cdylib");
+    let anchor = manifest
+        .get("dependencies")
+        .and_then(|d| d.as_table())
+        .is_some_and(|d| d.contains_key("anchor-lang"));
+    if !cdylib && !anchor {
+        return None;
+    }
+    lib.and_then(|l| l.get("name"))
+        .or_else(|| manifest.get("package")?.get("name"))
+        .and_then(|n| n.as_str())
+        .map(|n| n.replace('-', "_"))
+}
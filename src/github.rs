@@ -3,7 +3,7 @@ use serde_json::json;
 
 /// Format findings into a markdown report for PR comments.
 pub fn format_report(report: &AnalysisReport) -> String {
-    if report.findings.is_empty() {
+    if report.finding_count() == 0 {
         return format!(
             "## Anchor Security Report\n\n\
              No issues found across {} files scanned.\n\n\
@@ -12,63 +12,61 @@ pub fn format_report(report: &AnalysisReport) -> String {
         );
     }
 
-    let high: Vec<&Finding> = report
-        .findings
-        .iter()
-        .filter(|f| f.severity == Severity::High)
-        .collect();
-    let medium: Vec<&Finding> = report
-        .findings
-        .iter()
-        .filter(|f| f.severity == Severity::Medium)
-        .collect();
-    let low: Vec<&Finding> = report
-        .findings
-        .iter()
-        .filter(|f| f.severity == Severity::Low)
-        .collect();
-
     let mut md = format!(
         "## Anchor Security Report\n\n\
          **{} issue(s)** found across {} files scanned.\n\n",
-        report.findings.len(),
+        report.finding_count(),
         report.files_scanned
     );
 
-    if !high.is_empty() {
-        md.push_str("### High Severity\n\n");
-        for f in &high {
-            md.push_str(&format_finding(f));
+    // With several programs, each gets its own section
+    if let [program] = report.programs.as_slice() {
+        push_severity_sections(&mut md, &program.findings, "###");
+    } else {
+        for program in report.programs.iter().filter(|p| !p.findings.is_empty()) {
+            md.push_str(&format!(
+                "### `{}` ({} issue(s))\n\n",
+                program.name,
+                program.findings.len()
+            ));
+            push_severity_sections(&mut md, &program.findings, "####");
         }
-        md.push('\n');
     }
 
-    if !medium.is_empty() {
-        md.push_str("### Medium Severity\n\n");
-        for f in &medium {
-            md.push_str(&format_finding(f));
-        }
-        md.push('\n');
-    }
+    md.push_str("---\n*Generated by [anchor-audit-action](https://github.com/avhidotsol/anchor-audit-action)*");
+
+    md
+}
 
-    if !low.is_empty() {
-        md.push_str("### Low Severity\n\n");
-        for f in &low {
+fn push_severity_sections(md: &mut String, findings: &[Finding], heading: &str) {
+    for (severity, title) in [
+        (Severity::High, "High Severity"),
+        (Severity::Medium, "Medium Severity"),
+        (Severity::Low, "Low Severity"),
+    ] {
+        let matching: Vec<&Finding> = findings.iter().filter(|f| f.severity == severity).collect();
+        if matching.is_empty() {
+            continue;
+        }
+        md.push_str(&format!("{} {}\n\n", heading, title));
+        for f in matching {
             md.push_str(&format_finding(f));
         }
         md.push('\n');
     }
-
-    md.push_str("---\n*Generated by [anchor-audit-action](https://github.com/avhidotsol/anchor-audit-action)*");
-
-    md
 }
 
 fn format_finding(f: &Finding) -> String {
-    format!(
-        "- **`{}`** in `{}:{}`\n  {}\n",
-        f.check, f.file, f.line, f.message
-    )
+    match &f.context {
+        Some(context) => format!(
+            "- **`{}`** in `{}:{}` (`{}`)\n  {}\n",
+            f.check, f.file, f.line, context, f.message
+        ),
+        None => format!(
+            "- **`{}`** in `{}:{}`\n  {}\n",
+            f.check, f.file, f.line, f.message
+        ),
+    }
 }
 
 /// Post a comment on a pull request via the GitHub API.
@@ -116,28 +114,29 @@ pub async fn create_check_run(
         "failure"
     } else if report.has_medium_or_above() {
         "neutral"
-    } else if report.findings.is_empty() {
+    } else if report.finding_count() == 0 {
         "success"
     } else {
         "neutral"
     };
 
-    let summary = if report.findings.is_empty() {
-        format!(
-            "No issues found across {} files.",
-            report.files_scanned
-        )
+    let summary = if report.finding_count() == 0 {
+        format!("No issues found across {} files.", report.files_scanned)
     } else {
         format!(
             "{} issue(s) found across {} files.",
-            report.findings.len(),
+            report.finding_count(),
             report.files_scanned
         )
     };
 
-    let annotations: Vec<serde_json::Value> = report
-        .findings
-        .iter()
+    // Each program's findings are sorted on their own, so rank them across
+    // programs before capping or later programs' High findings are dropped
+    let mut findings: Vec<&Finding> = report.findings().collect();
+    findings.sort_by_key(|f| f.severity);
+
+    let annotations: Vec<serde_json::Value> = findings
+        .into_iter()
         .take(50) // GitHub limits annotations to 50 per request
         .map(|f| {
             json!({
@@ -182,10 +181,7 @@ pub async fn create_check_run(
         let status = resp.status();
         let text = resp.text().await.unwrap_or_default();
         // Check runs require specific permissions — log but don't fail
-        eprintln!(
-            "Warning: Could not create check run ({}): {}",
-            status, text
-        );
+        eprintln!("Warning: Could not create check run ({}): {}", status, text);
     }
 
     Ok(())
@@ -195,9 +191,9 @@ pub async fn create_check_run(
 pub fn get_pr_number_from_event(event_path: &str) -> Option<u64> {
     let data = std::fs::read_to_string(event_path).ok()?;
     let event: serde_json::Value = serde_json::from_str(&data).ok()?;
-    event["pull_request"]["number"].as_u64().or_else(|| {
-        event["number"].as_u64()
-    })
+    event["pull_request"]["number"]
+        .as_u64()
+        .or_else(|| event["number"].as_u64())
 }
 
 /// Extract the head SHA from the GitHub event payload.
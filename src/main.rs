@@ -4,6 +4,10 @@ mod github;
 use std::path::PathBuf;
 use std::process;
 
+/// The deepest `call_depth` accepted. Each level multiplies the helpers
+/// inlined into a function, so deeper settings cost far more than they find.
+const MAX_CALL_DEPTH: usize = 5;
+
 #[tokio::main]
 async fn main() {
     let scan_path = std::env::var("INPUT_PATH")
@@ -11,21 +15,40 @@ async fn main() {
         .unwrap_or_else(|_| ".".into());
 
     let fail_on = std::env::var("INPUT_FAIL_ON").unwrap_or_else(|_| "high".into());
+    let scan_all = std::env::var("INPUT_SCAN_ALL").is_ok_and(|v| v == "true");
+    let call_depth = std::env::var("INPUT_CALL_DEPTH")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(3);
+    if call_depth > MAX_CALL_DEPTH {
+        eprintln!(
+            "anchor-audit: call_depth {} is above the maximum, using {}",
+            call_depth, MAX_CALL_DEPTH
+        );
+    }
+    let call_depth = call_depth.min(MAX_CALL_DEPTH);
 
     eprintln!("anchor-audit: scanning {}", scan_path);
 
-    // Discover and analyze files
+    // Discover program crates and analyze their sources
     let root = PathBuf::from(&scan_path);
-    let files = analyzer::discover_rust_files(&root);
+    let programs = analyzer::workspace::discover_programs(&root, scan_all);
+    let file_count: usize = programs.iter().map(|p| p.files.len()).sum();
 
-    if files.is_empty() {
+    if file_count == 0 {
         eprintln!("anchor-audit: no Rust files found under {}", scan_path);
         process::exit(0);
     }
 
-    eprintln!("anchor-audit: found {} Rust files", files.len());
+    for program in &programs {
+        eprintln!(
+            "anchor-audit: found program {} ({} Rust files)",
+            program.name,
+            program.files.len()
+        );
+    }
 
-    let report = analyzer::analyze(&files);
+    let report = analyzer::analyze(&programs, call_depth);
 
     // Print JSON report to stdout
     let json_output = serde_json::to_string_pretty(&report).expect("Failed to serialize report");
@@ -50,8 +73,7 @@ async fn main() {
             if let Some(event_path) = event_path.as_ref() {
                 if let Some(pr_number) = github::get_pr_number_from_event(event_path) {
                     eprintln!("anchor-audit: posting comment on PR #{}", pr_number);
-                    if let Err(e) =
-                        github::post_pr_comment(token, repo, pr_number, &markdown).await
+                    if let Err(e) = github::post_pr_comment(token, repo, pr_number, &markdown).await
                     {
                         eprintln!("anchor-audit: failed to post PR comment: {}", e);
                     }
@@ -60,15 +82,15 @@ async fn main() {
                 // Create check run if we have a head SHA
                 if let Some(sha) = github::get_head_sha_from_event(event_path) {
                     eprintln!("anchor-audit: creating check run for {}", &sha[..8]);
-                    if let Err(e) =
-                        github::create_check_run(token, repo, &sha, &report).await
-                    {
+                    if let Err(e) = github::create_check_run(token, repo, &sha, &report).await {
                         eprintln!("anchor-audit: failed to create check run: {}", e);
                     }
                 }
             }
         } else {
-            eprintln!("anchor-audit: GITHUB_TOKEN or GITHUB_REPOSITORY not set, skipping PR integration");
+            eprintln!(
+                "anchor-audit: GITHUB_TOKEN or GITHUB_REPOSITORY not set, skipping PR integration"
+            );
         }
     }
 
@@ -79,7 +101,7 @@ async fn main() {
                 &output_file,
                 format!(
                     "finding-count={}\nhas-high={}\nhas-medium={}\n",
-                    report.findings.len(),
+                    report.finding_count(),
                     report.has_high(),
                     report.has_medium_or_above(),
                 ),
@@ -91,7 +113,7 @@ async fn main() {
     let should_fail = match fail_on.as_str() {
         "high" => report.has_high(),
         "medium" => report.has_medium_or_above(),
-        "low" => !report.findings.is_empty(),
+        "low" => report.finding_count() > 0,
         "none" => false,
         _ => report.has_high(),
     };
@@ -99,7 +121,7 @@ async fn main() {
     if should_fail {
         eprintln!(
             "anchor-audit: failing with {} issue(s) (fail_on={})",
-            report.findings.len(),
+            report.finding_count(),
             fail_on
         );
         process::exit(1);
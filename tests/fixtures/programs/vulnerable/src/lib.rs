@@ -35,6 +35,25 @@ pub mod vulnerable {
 
         Ok(())
     }
+
+    pub fn read_config(ctx: Context<ReadConfig>) -> Result<()> {
+        // Vulnerable: raw account data deserialized without an owner check
+        let data = ctx.accounts.config.try_borrow_data()?;
+        let config = Config::try_deserialize(&mut &data[..])?;
+        msg!("admin: {}", config.admin);
+
+        Ok(())
+    }
+
+    pub fn log_config(ctx: Context<ReadConfig>) -> Result<()> {
+        // Vulnerable: logging the owner does not check it
+        msg!("owner: {}", ctx.accounts.config.owner);
+        let data = ctx.accounts.config.try_borrow_data()?;
+        let config = Config::try_deserialize(&mut &data[..])?;
+        msg!("admin: {}", config.admin);
+
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
@@ -45,6 +64,19 @@ pub struct Withdraw<'info> {
     pub authority: Signer<'info>,
 }
 
+impl<'info> Withdraw<'info> {
+    // Vulnerable: invoke_signed in an impl method without bump validation
+    pub fn transfer(&self, amount: u64) -> Result<()> {
+        let signer_seeds: &[&[&[u8]]] = &[&[b"vault"]];
+        invoke_signed(
+            &system_instruction::transfer(self.vault.key, self.recipient.key, amount),
+            &[self.vault.clone(), self.recipient.clone()],
+            signer_seeds,
+        )?;
+        Ok(())
+    }
+}
+
 #[derive(Accounts)]
 pub struct Initialize<'info> {
     // Vulnerable: #[account] without constraints
@@ -56,6 +88,12 @@ pub struct Initialize<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct ReadConfig<'info> {
+    /// CHECK: deserialized manually in the handler
+    pub config: AccountInfo<'info>,
+}
+
 #[account]
 pub struct Config {
     pub admin: Pubkey,